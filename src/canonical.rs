@@ -0,0 +1,97 @@
+use serde_json::{Number, Value};
+use std::io::{self, Write};
+
+/// Write a value in the JSON Canonicalization Scheme (RFC 8785)
+///
+/// The traversal of `dedup` is not reused: it mutates arrays after their elements and visits
+/// keys in map order, while this writes values before their elements and sorts the keys.
+pub(crate) fn write<W: Write>(val: &Value, w: &mut W) -> io::Result<()> {
+    match val {
+        Value::Null => w.write_all(b"null"),
+        Value::Bool(b) => w.write_all(if *b { b"true" } else { b"false" }),
+        Value::Number(n) => w.write_all(number(n).as_bytes()),
+        Value::String(s) => string(s, w),
+        Value::Array(arr) => {
+            w.write_all(b"[")?;
+            for (i, v) in arr.iter().enumerate() {
+                if i > 0 {
+                    w.write_all(b",")?;
+                }
+                write(v, w)?;
+            }
+            w.write_all(b"]")
+        }
+        Value::Object(obj) => {
+            let mut entries = obj.iter().collect::<Vec<_>>();
+            entries.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+            w.write_all(b"{")?;
+            for (i, (k, v)) in entries.into_iter().enumerate() {
+                if i > 0 {
+                    w.write_all(b",")?;
+                }
+                string(k, w)?;
+                w.write_all(b":")?;
+                write(v, w)?;
+            }
+            w.write_all(b"}")
+        }
+    }
+}
+
+/// Write a string, escaping only what JSON requires
+fn string<W: Write>(s: &str, w: &mut W) -> io::Result<()> {
+    w.write_all(b"\"")?;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        let escape = match c {
+            '"' => "\\\"",
+            '\\' => "\\\\",
+            '\u{8}' => "\\b",
+            '\u{c}' => "\\f",
+            '\n' => "\\n",
+            '\r' => "\\r",
+            '\t' => "\\t",
+            '\u{0}'..='\u{1f}' => "",
+            _ => continue,
+        };
+        w.write_all(&s.as_bytes()[start..i])?;
+        if escape.is_empty() {
+            write!(w, "\\u{:04x}", c as u32)?;
+        } else {
+            w.write_all(escape.as_bytes())?;
+        }
+        start = i + c.len_utf8();
+    }
+    w.write_all(&s.as_bytes()[start..])?;
+    w.write_all(b"\"")
+}
+
+/// Format a number like ECMAScript's `Number.prototype.toString`, as a double
+fn number(n: &Number) -> std::string::String {
+    let f = n.as_f64().unwrap_or(0.0);
+    if f == 0.0 {
+        return "0".to_string();
+    }
+    // shortest digits that round-trip, and the exponent of the first one
+    let sci = format!("{:e}", f.abs());
+    let (mantissa, exp) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    let n = exp.parse::<i32>().unwrap_or(0) + 1;
+    let sign = if f < 0.0 { "-" } else { "" };
+    let body = if k <= n && n <= 21 {
+        format!("{}{}", digits, "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{}", "0".repeat(-n as usize), digits)
+    } else {
+        let e = if n > 0 { format!("e+{}", n - 1) } else { format!("e-{}", 1 - n) };
+        if k == 1 {
+            format!("{}{}", digits, e)
+        } else {
+            format!("{}.{}{}", &digits[..1], &digits[1..], e)
+        }
+    };
+    format!("{}{}", sign, body)
+}
@@ -0,0 +1,298 @@
+use crate::{
+    merge::merge_similar_objects,
+    pointer::{self, PathPattern},
+    utils::{Compare, DedupeHashValue},
+};
+use serde_json::Value::{self, *};
+use std::collections::HashMap;
+
+/// How strings are compared by [`JsonUtils::dedup_with`](crate::JsonUtils::dedup_with).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum StringCompare {
+    /// Strings are equal only if they are identical.
+    #[default]
+    CaseSensitive,
+    /// Strings are equal if their lowercase forms are identical.
+    CaseInsensitive,
+    /// Strings are equal if their full Unicode case foldings are identical, so `"Straße"` equals `"STRASSE"`
+    /// and `"ﬁle"` equals `"FILE"`.
+    CaseFolded,
+}
+
+/// How numbers are compared by [`JsonUtils::dedup_with`](crate::JsonUtils::dedup_with)
+/// and [`JsonUtils::merge_similar_with`](crate::JsonUtils::merge_similar_with).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum NumberCompare {
+    /// Numbers are equal only if they have the same representation, so `1` differs from `1.0`.
+    #[default]
+    Exact,
+    /// Numbers are equal if they have the same mathematical value, so `1`, `1.0` and `1e0` are
+    /// equal, as are `-0.0` and `0.0`.
+    Semantic,
+}
+
+/// How arrays are compared by [`JsonUtils::dedup_with`](crate::JsonUtils::dedup_with),
+/// [`JsonUtils::merge_similar_with`](crate::JsonUtils::merge_similar_with) and
+/// [`JsonUtils::diff_with`](crate::JsonUtils::diff_with).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ArrayCompare {
+    /// Arrays are equal if their elements are equal in the same order.
+    #[default]
+    Ordered,
+    /// Arrays are equal if they have equal elements the same number of times, in any order,
+    /// so `["a", "b"]` equals `["b", "a"]` but not `["a", "b", "b"]`.
+    Multiset,
+    /// Arrays are equal if every element of each one has an equal in the other, so `["a", "b"]`
+    /// equals `["b", "a", "a"]`.
+    Set,
+}
+
+/// Which element [`JsonUtils::dedup_by_keys`](crate::JsonUtils::dedup_by_keys) keeps out of a group of duplicates.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Keep {
+    /// The first element, at its position.
+    First,
+    /// The last element, at its position.
+    Last,
+    /// The elements merged together like `merge_similar` does, at the position of the first one.
+    /// Elements that cannot be merged with the first one are kept as they are.
+    Merge,
+}
+
+/// Which arrays [`JsonUtils::dedup_with`](crate::JsonUtils::dedup_with) deduplicates.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum DedupScope {
+    /// Every array, at any depth.
+    #[default]
+    All,
+    /// Only the value itself, when it is an array.
+    TopLevel,
+    /// Arrays nested in at most the given number of arrays or objects, so `MaxDepth(0)` is the same as `TopLevel`.
+    MaxDepth(usize),
+    /// Arrays at the given JSON pointers, in which a `*` token matches any single
+    /// key or index and a `**` token matches any number of them.
+    Paths(Vec<std::string::String>),
+}
+
+/// Options for [`JsonUtils::dedup_with`](crate::JsonUtils::dedup_with).
+#[derive(Clone, Debug, Default)]
+pub struct DedupOptions {
+    pub(crate) compare: Compare,
+    pub(crate) scope: DedupScope,
+    pub(crate) paths: Vec<PathPattern>,
+}
+
+impl DedupOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how strings are compared, `StringCompare::CaseSensitive` by default.
+    pub fn strings(mut self, strings: StringCompare) -> Self {
+        self.compare.strings = strings;
+        self
+    }
+
+    /// When set, leading and trailing whitespace of strings is ignored and inner runs of
+    /// whitespace compare equal to a single space.
+    pub fn normalize_whitespace(mut self, normalize: bool) -> Self {
+        self.compare.whitespace = normalize;
+        self
+    }
+
+    /// Sets how numbers are compared, `NumberCompare::Exact` by default.
+    pub fn numbers(mut self, numbers: NumberCompare) -> Self {
+        self.compare.numbers = numbers;
+        self
+    }
+
+    /// Considers numbers equal when they differ by at most `epsilon`, regardless of how they are written.
+    pub fn epsilon(mut self, epsilon: f64) -> Self {
+        self.compare.epsilon = Some(epsilon);
+        self
+    }
+
+    /// Sets how arrays nested in the compared elements are compared, `ArrayCompare::Ordered` by default.
+    pub fn arrays(mut self, arrays: ArrayCompare) -> Self {
+        self.compare.arrays = arrays;
+        self
+    }
+
+    /// Sets how arrays at JSON pointers matching the pattern are compared. Pointers are relative
+    /// to the compared elements, so `/tags` refers to the `tags` member of every element.
+    /// Panics if the pattern is not a JSON pointer.
+    pub fn arrays_at(mut self, pattern: &str, arrays: ArrayCompare) -> Self {
+        self.compare.arrays_at.push((PathPattern::new(pattern), arrays));
+        self
+    }
+
+    /// Sets which arrays are deduplicated, `DedupScope::All` by default.
+    /// Elements are always compared as a whole, including the arrays they contain.
+    /// Panics if a pattern of `DedupScope::Paths` is not a JSON pointer.
+    pub fn scope(mut self, scope: DedupScope) -> Self {
+        self.paths = match &scope {
+            DedupScope::Paths(paths) => paths.iter().map(|p| PathPattern::new(p)).collect(),
+            _ => vec![],
+        };
+        self.scope = scope;
+        self
+    }
+}
+
+/// Element kept by [`JsonUtils::dedup_with_counts`](crate::JsonUtils::dedup_with_counts) and its removed duplicates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Kept {
+    /// Index of the element in the array before deduplication.
+    pub index: usize,
+    /// Indices of the removed duplicates of the element in the array before deduplication.
+    pub duplicates: Vec<usize>,
+}
+
+impl Kept {
+    /// Number of copies of the element, including itself.
+    pub fn count(&self) -> usize {
+        self.duplicates.len() + 1
+    }
+}
+
+/// Result of the deduplication of one array by [`JsonUtils::dedup_with_counts`](crate::JsonUtils::dedup_with_counts).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DedupReport {
+    /// JSON pointer of the array, using indices before deduplication.
+    pub path: std::string::String,
+    /// Elements kept in the array, in order.
+    pub kept: Vec<Kept>,
+}
+
+/// `Dedup` arrays of a value in the scope of the options, innermost arrays first
+pub(crate) fn dedup(val: &mut Value, options: &DedupOptions, report: Option<&mut Vec<DedupReport>>) {
+    dedup_at(val, options, "", 0, report);
+}
+
+fn dedup_at(
+    val: &mut Value,
+    options: &DedupOptions,
+    path: &str,
+    depth: usize,
+    mut report: Option<&mut Vec<DedupReport>>,
+) {
+    let (in_scope, recurse) = match &options.scope {
+        DedupScope::All => (true, true),
+        DedupScope::TopLevel => (depth == 0, false),
+        DedupScope::MaxDepth(max) => (depth <= *max, depth < *max),
+        DedupScope::Paths(_) => (options.paths.iter().any(|p| p.matches(path)), true),
+    };
+    // paths are only needed to match the patterns and for the report
+    let track = report.is_some() || matches!(options.scope, DedupScope::Paths(_));
+    let child_path = |token: &str| {
+        if track {
+            pointer::push(path, token)
+        } else {
+            std::string::String::new()
+        }
+    };
+    match val {
+        Array(arr) => {
+            if recurse {
+                for (i, v) in arr.iter_mut().enumerate() {
+                    dedup_at(v, options, &child_path(&i.to_string()), depth + 1, report.as_deref_mut());
+                }
+            }
+            if !in_scope {
+                return;
+            }
+            let (keep, kept) = {
+                let mut index: HashMap<DedupeHashValue, usize> = HashMap::new();
+                let mut kept: Vec<Kept> = vec![];
+                let keep = arr
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| match index.get(&DedupeHashValue(v, &options.compare)) {
+                        Some(&k) => {
+                            kept[k].duplicates.push(i);
+                            false
+                        }
+                        None => {
+                            index.insert(DedupeHashValue(v, &options.compare), kept.len());
+                            kept.push(Kept { index: i, duplicates: vec![] });
+                            true
+                        }
+                    })
+                    .collect::<Vec<bool>>();
+                (keep, kept)
+            };
+            let mut keep = keep.into_iter();
+            arr.retain(|_| keep.next().unwrap_or(false));
+            if let Some(report) = report {
+                report.push(DedupReport { path: path.to_string(), kept });
+            }
+        }
+        Object(obj) if recurse => {
+            for (k, v) in obj.iter_mut() {
+                dedup_at(v, options, &child_path(k), depth + 1, report.as_deref_mut());
+            }
+        }
+        _ => {}
+    }
+}
+
+/// `Dedup` the elements of an array having equal values at all the given pointers
+pub(crate) fn dedup_by_keys(val: &mut Value, keys: &[&str], keep: Keep) {
+    let arr = match val {
+        Array(arr) if !keys.is_empty() => arr,
+        _ => return,
+    };
+    let compare = Compare::default();
+    // indices of the elements of every group of duplicates
+    let groups = {
+        let mut groups: Vec<Vec<usize>> = vec![];
+        let mut index: HashMap<Vec<DedupeHashValue>, usize> = HashMap::new();
+        for (i, v) in arr.iter().enumerate() {
+            // elements missing one of the keys are never duplicates
+            let key = keys
+                .iter()
+                .map(|k| v.pointer(k).map(|kv| DedupeHashValue(kv, &compare)))
+                .collect::<Option<Vec<DedupeHashValue>>>();
+            match key {
+                Some(key) => match index.get(&key) {
+                    Some(&g) => groups[g].push(i),
+                    None => {
+                        index.insert(key, groups.len());
+                        groups.push(vec![i]);
+                    }
+                },
+                None => groups.push(vec![i]),
+            }
+        }
+        groups
+    };
+
+    let mut slots = arr.drain(..).map(Some).collect::<Vec<Option<Value>>>();
+    for group in groups {
+        match keep {
+            Keep::First => {
+                for &i in &group[1..] {
+                    slots[i] = None;
+                }
+            }
+            Keep::Last => {
+                for &i in &group[..group.len() - 1] {
+                    slots[i] = None;
+                }
+            }
+            Keep::Merge => {
+                let mut merged = slots[group[0]].take();
+                for &i in &group[1..] {
+                    if let (Some(m), Some(v)) = (&merged, &slots[i]) {
+                        if let Ok(m) = merge_similar_objects(m, v, &compare) {
+                            merged = Some(m);
+                            slots[i] = None;
+                        }
+                    }
+                }
+                slots[group[0]] = merged;
+            }
+        }
+    }
+    arr.extend(slots.into_iter().flatten());
+}
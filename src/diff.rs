@@ -0,0 +1,220 @@
+use crate::{
+    dedup::ArrayCompare,
+    pointer::{self, PathPattern},
+    utils::Compare,
+};
+use serde_json::Value::{self, *};
+use std::fmt;
+
+/// Difference between two values found by [`JsonUtils::diff`](crate::JsonUtils::diff).
+#[derive(Clone, Debug, PartialEq)]
+pub enum Difference {
+    /// A field or element only present in the other value.
+    Added { path: std::string::String, value: Value },
+    /// A field or element missing from the other value.
+    Removed { path: std::string::String, value: Value },
+    /// A value changed to another value of the same type.
+    Changed {
+        path: std::string::String,
+        from: Value,
+        to: Value,
+    },
+    /// A value changed to a value of another type.
+    TypeChanged {
+        path: std::string::String,
+        from: Value,
+        to: Value,
+    },
+}
+
+impl Difference {
+    /// JSON pointer of the difference.
+    pub fn path(&self) -> &str {
+        match self {
+            Difference::Added { path, .. }
+            | Difference::Removed { path, .. }
+            | Difference::Changed { path, .. }
+            | Difference::TypeChanged { path, .. } => path,
+        }
+    }
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = pointer::display(self.path());
+        match self {
+            Difference::Added { value, .. } => write!(f, "+ {}: {}", path, value),
+            Difference::Removed { value, .. } => write!(f, "- {}: {}", path, value),
+            Difference::Changed { from, to, .. } => write!(f, "~ {}: {} -> {}", path, from, to),
+            Difference::TypeChanged { from, to, .. } => write!(
+                f,
+                "! {}: {} {} -> {} {}",
+                path,
+                pointer::kind(from),
+                from,
+                pointer::kind(to),
+                to
+            ),
+        }
+    }
+}
+
+/// Text report of differences, one per line.
+/// ## Example
+/// ```rust
+/// use serde_json::json;
+/// use serde_json_utils::{DiffReport, JsonUtils};
+///
+/// let diff = json!({"a": 1, "b": [1, 2], "c": "x"}).diff(&json!({"a": 2, "b": [1], "c": 1, "d": null}));
+/// assert_eq!(DiffReport(&diff).to_string(), "~ /a: 1 -> 2\n- /b/1: 2\n! /c: string \"x\" -> number 1\n+ /d: null\n");
+/// ```
+pub struct DiffReport<'a>(pub &'a [Difference]);
+
+impl fmt::Display for DiffReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for d in self.0 {
+            writeln!(f, "{}", d)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options for [`JsonUtils::diff_with`](crate::JsonUtils::diff_with).
+#[derive(Clone, Debug, Default)]
+pub struct DiffOptions {
+    pub(crate) compare: Compare,
+    pub(crate) ignore_order: bool,
+    pub(crate) ignore: Vec<PathPattern>,
+}
+
+impl DiffOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Compares arrays regardless of the order of their elements: an element is only reported
+    /// as removed or added, at its index, when it has no equal in the other array.
+    pub fn ignore_array_order(mut self, ignore: bool) -> Self {
+        self.ignore_order = ignore;
+        self
+    }
+
+    /// Ignores the values at JSON pointers matching the pattern, in which a `*` token matches
+    /// any single key or index and a `**` token matches any number of them. Panics if the pattern is not a JSON pointer.
+    pub fn ignore(mut self, pattern: &str) -> Self {
+        self.ignore.push(PathPattern::new(pattern));
+        self
+    }
+
+    /// Sets how arrays are compared, `ArrayCompare::Ordered` by default. Arrays compared as
+    /// multisets are diffed like with `ignore_array_order`, and arrays compared as sets only
+    /// report the elements having no equal in the other array.
+    pub fn arrays(mut self, arrays: ArrayCompare) -> Self {
+        self.compare.arrays = arrays;
+        self
+    }
+
+    /// Sets how arrays at JSON pointers matching the pattern are compared. Panics if the pattern is not a JSON pointer.
+    pub fn arrays_at(mut self, pattern: &str, arrays: ArrayCompare) -> Self {
+        self.compare.arrays_at.push((PathPattern::new(pattern), arrays));
+        self
+    }
+
+    /// Considers numbers equal when they differ by at most `epsilon`.
+    pub fn epsilon(mut self, epsilon: f64) -> Self {
+        self.compare.epsilon = Some(epsilon);
+        self
+    }
+
+    fn is_ignored(&self, path: &str) -> bool {
+        self.ignore.iter().any(|p| p.matches(path))
+    }
+
+    /// Adds an added or removed value to the differences, unless its path is ignored
+    fn report(&self, res: &mut Vec<Difference>, d: Difference) {
+        if !self.is_ignored(d.path()) {
+            res.push(d);
+        }
+    }
+}
+
+/// List the differences between `a` and `b`
+pub(crate) fn diff(a: &Value, b: &Value, options: &DiffOptions) -> Vec<Difference> {
+    let mut res = vec![];
+    diff_at(a, b, "", options, &mut res);
+    res
+}
+
+fn diff_at(a: &Value, b: &Value, path: &str, options: &DiffOptions, res: &mut Vec<Difference>) {
+    if options.is_ignored(path) {
+        return;
+    }
+    match (a, b) {
+        (Object(o1), Object(o2)) => {
+            for (k, v1) in o1 {
+                let p = pointer::push(path, k);
+                match o2.get(k) {
+                    Some(v2) => diff_at(v1, v2, &p, options, res),
+                    None => options.report(res, Difference::Removed { path: p, value: v1.clone() }),
+                }
+            }
+            for (k, v2) in o2 {
+                if !o1.contains_key(k) {
+                    let p = pointer::push(path, k);
+                    options.report(res, Difference::Added { path: p, value: v2.clone() });
+                }
+            }
+        }
+        (Array(a1), Array(a2)) if options.ignore_order || options.compare.arrays_for(path) != ArrayCompare::Ordered => {
+            // elements of sets can be paired with several equal elements
+            let set = options.compare.arrays_for(path) == ArrayCompare::Set;
+            let same = |v1: &Value, v2: &Value, p: &str| {
+                let mut inner = vec![];
+                diff_at(v1, v2, p, options, &mut inner);
+                inner.is_empty()
+            };
+            // pair every element with the first equal unpaired element of the other array
+            let mut paired = vec![false; a2.len()];
+            for (i, v1) in a1.iter().enumerate() {
+                let p = pointer::push(path, &i.to_string());
+                match (0..a2.len()).find(|&j| (set || !paired[j]) && same(v1, &a2[j], &p)) {
+                    Some(j) => paired[j] = true,
+                    None => options.report(res, Difference::Removed { path: p, value: v1.clone() }),
+                }
+            }
+            for (j, v2) in a2.iter().enumerate().filter(|(j, _)| !paired[*j]) {
+                let p = pointer::push(path, &j.to_string());
+                if !(set && a1.iter().any(|v1| same(v1, v2, &p))) {
+                    options.report(res, Difference::Added { path: p, value: v2.clone() });
+                }
+            }
+        }
+        (Array(a1), Array(a2)) => {
+            for (i, (v1, v2)) in a1.iter().zip(a2).enumerate() {
+                diff_at(v1, v2, &pointer::push(path, &i.to_string()), options, res);
+            }
+            for (i, v1) in a1.iter().enumerate().skip(a2.len()) {
+                let p = pointer::push(path, &i.to_string());
+                options.report(res, Difference::Removed { path: p, value: v1.clone() });
+            }
+            for (j, v2) in a2.iter().enumerate().skip(a1.len()) {
+                let p = pointer::push(path, &j.to_string());
+                options.report(res, Difference::Added { path: p, value: v2.clone() });
+            }
+        }
+        _ if pointer::kind(a) != pointer::kind(b) => res.push(Difference::TypeChanged {
+            path: path.to_string(),
+            from: a.clone(),
+            to: b.clone(),
+        }),
+        _ => {
+            if !options.compare.eq(a, b) {
+                res.push(Difference::Changed {
+                    path: path.to_string(),
+                    from: a.clone(),
+                    to: b.clone(),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,67 @@
+use crate::pointer::display;
+use thiserror::Error;
+
+/// Errors returned by the fallible `try_*` operations of [`JsonUtils`](crate::JsonUtils).
+///
+/// Variants locating a failure in a value carry in `path` the JSON pointer (RFC 6901) of the
+/// node where it happened; the empty string refers to the root value, shown as `(root)` in messages.
+#[derive(Debug, Error)]
+pub enum Error {
+    /// The value could not be deserialized into the requested type.
+    #[error("{}: {message}", display(.path))]
+    Deserialize { path: String, message: String },
+
+    /// The value does not have the shape an operation requires.
+    #[error("{}: expected {expected}, found {found}", display(.path))]
+    ShapeMismatch {
+        path: String,
+        expected: &'static str,
+        found: &'static str,
+    },
+
+    /// Two values could not be merged together.
+    #[error("{}: cannot merge incompatible values", display(.path))]
+    IncompatibleMerge { path: String },
+
+    /// A string is not a valid JSON pointer.
+    #[error("{}: invalid JSON pointer", display(.path))]
+    InvalidPointer { path: String },
+
+    /// A JSON pointer does not point to an existing value.
+    #[error("{}: path not found", display(.path))]
+    PathNotFound { path: String },
+
+    /// A string is not a valid JSONPath query (RFC 9535).
+    #[error("invalid JSONPath query {query:?}, {message}")]
+    InvalidQuery { query: String, message: String },
+
+    /// A key of a flattened object is malformed.
+    #[error("invalid flattened key {key:?}")]
+    InvalidKey { key: String },
+
+    /// A separator of flattened keys is not allowed.
+    #[error("invalid flattening separator {separator:?}")]
+    InvalidSeparator { separator: String },
+
+    /// Two keys of an object would be renamed to the same key, the one at `path`.
+    #[error("{}: keys {first:?} and {second:?} collide", display(.path))]
+    KeyCollision {
+        path: String,
+        first: String,
+        second: String,
+    },
+
+    /// A JSON Patch `test` operation failed.
+    #[error("{}: test failed", display(.path))]
+    TestFailed { path: String },
+
+    /// A configured limit (such as the maximum nesting depth) was exceeded.
+    #[error("{}: {what} limit of {limit} exceeded", display(.path))]
+    LimitExceeded {
+        path: String,
+        what: &'static str,
+        limit: usize,
+    },
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
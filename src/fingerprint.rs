@@ -0,0 +1,162 @@
+use crate::{
+    dedup::{ArrayCompare, NumberCompare, StringCompare},
+    pointer::PathPattern,
+    utils::{integral, Compare},
+};
+use serde_json::{Number, Value};
+use sha2::{Digest, Sha256};
+use std::fmt;
+
+/// Stable 256-bit digest of a value, computed by [`JsonUtils::fingerprint`](crate::JsonUtils::fingerprint).
+///
+/// Fingerprints do not depend on the order of object keys nor on the platform, so they can be
+/// stored and compared across processes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Fingerprint(pub [u8; 32]);
+
+impl Fingerprint {
+    /// Bytes of the digest.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+
+    /// First 128 bits of the digest, for storage where 256 bits are too many.
+    pub fn to_u128(&self) -> u128 {
+        let mut bytes = [0; 16];
+        bytes.copy_from_slice(&self.0[..16]);
+        u128::from_be_bytes(bytes)
+    }
+
+    /// Lowercase hexadecimal form of the digest.
+    pub fn to_hex(&self) -> String {
+        self.to_string()
+    }
+}
+
+impl fmt::Display for Fingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// Options for [`JsonUtils::fingerprint_with`](crate::JsonUtils::fingerprint_with), the comparisons
+/// of [`DedupOptions`](crate::DedupOptions) that a digest can honor.
+#[derive(Clone, Debug, Default)]
+pub struct FingerprintOptions {
+    pub(crate) compare: Compare,
+}
+
+impl FingerprintOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how strings are compared, `StringCompare::CaseSensitive` by default.
+    pub fn strings(mut self, strings: StringCompare) -> Self {
+        self.compare.strings = strings;
+        self
+    }
+
+    /// When set, leading and trailing whitespace of strings is ignored and inner runs of
+    /// whitespace compare equal to a single space.
+    pub fn normalize_whitespace(mut self, normalize: bool) -> Self {
+        self.compare.whitespace = normalize;
+        self
+    }
+
+    /// Sets how numbers are compared, `NumberCompare::Exact` by default.
+    pub fn numbers(mut self, numbers: NumberCompare) -> Self {
+        self.compare.numbers = numbers;
+        self
+    }
+
+    /// Sets how arrays are compared, `ArrayCompare::Ordered` by default.
+    pub fn arrays(mut self, arrays: ArrayCompare) -> Self {
+        self.compare.arrays = arrays;
+        self
+    }
+
+    /// Sets how arrays at JSON pointers matching the pattern are compared. Panics if the pattern is not a JSON pointer.
+    pub fn arrays_at(mut self, pattern: &str, arrays: ArrayCompare) -> Self {
+        self.compare.arrays_at.push((PathPattern::new(pattern), arrays));
+        self
+    }
+}
+
+/// Digest of a value, made of the digests of its elements so that values equal
+/// according to `compare` have the same digest.
+pub(crate) fn fingerprint(val: &Value, compare: &Compare) -> Fingerprint {
+    fingerprint_at(val, compare, "")
+}
+
+fn fingerprint_at(val: &Value, compare: &Compare, path: &str) -> Fingerprint {
+    let mut hasher = Sha256::new();
+    match val {
+        Value::Null => hasher.update(b"z"),
+        Value::Bool(b) => hasher.update(if *b { b"t" } else { b"f" }),
+        Value::Number(n) => number(n, compare, &mut hasher),
+        Value::String(s) => {
+            hasher.update(b"s");
+            hasher.update(compare.normalize_str(s).as_bytes());
+        }
+        Value::Array(arr) => {
+            let mut digests = arr
+                .iter()
+                .enumerate()
+                .map(|(i, v)| fingerprint_at(v, compare, &compare.child(path, i)))
+                .collect::<Vec<Fingerprint>>();
+            match compare.arrays_for(path) {
+                ArrayCompare::Ordered => hasher.update(b"a"),
+                ArrayCompare::Multiset => {
+                    digests.sort_unstable();
+                    hasher.update(b"m");
+                }
+                ArrayCompare::Set => {
+                    digests.sort_unstable();
+                    digests.dedup();
+                    hasher.update(b"e");
+                }
+            }
+            hasher.update((digests.len() as u64).to_be_bytes());
+            for d in digests {
+                hasher.update(d.0);
+            }
+        }
+        Value::Object(obj) => {
+            let mut entries = obj.iter().collect::<Vec<_>>();
+            entries.sort_by_key(|(k, _)| *k);
+            hasher.update(b"o");
+            hasher.update((entries.len() as u64).to_be_bytes());
+            for (k, v) in entries {
+                hasher.update((k.len() as u64).to_be_bytes());
+                hasher.update(k.as_bytes());
+                hasher.update(fingerprint_at(v, compare, &compare.child(path, k)).0);
+            }
+        }
+    }
+    Fingerprint(hasher.finalize().into())
+}
+
+fn number(n: &Number, compare: &Compare, hasher: &mut Sha256) {
+    let float = |x: f64| if x == 0.0 { 0f64.to_bits() } else { x.to_bits() };
+    if compare.numbers == NumberCompare::Semantic {
+        if let Some(x) = integral(n) {
+            hasher.update(b"n");
+            hasher.update(x.to_be_bytes());
+            return;
+        }
+    } else if let Some(x) = n.as_u64() {
+        hasher.update(b"u");
+        hasher.update(x.to_be_bytes());
+        return;
+    } else if let Some(x) = n.as_i64() {
+        hasher.update(b"i");
+        hasher.update(x.to_be_bytes());
+        return;
+    }
+    hasher.update(b"d");
+    hasher.update(float(n.as_f64().unwrap_or(0.0)).to_be_bytes());
+}
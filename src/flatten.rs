@@ -0,0 +1,311 @@
+use crate::{
+    error::{Error, Result},
+    pointer,
+};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+
+/// Maximum number of `null` elements added to an array by a single flattened key
+const MAX_PADDING: usize = 1 << 16;
+
+/// How [`JsonUtils::flatten`](crate::JsonUtils::flatten) writes array indices.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ArrayIndexStyle {
+    /// Indices are segments like keys, as in `a.0.b`. Keys made only of digits are escaped, as in `a.\0`.
+    #[default]
+    Dot,
+    /// Indices follow their array in brackets, as in `a[0].b`.
+    Brackets,
+}
+
+/// Options for [`JsonUtils::flatten`](crate::JsonUtils::flatten) and [`JsonUtils::unflatten`](crate::JsonUtils::unflatten).
+///
+/// In flattened keys a backslash escapes each character of the separator, brackets, digit-only
+/// keys and itself, and `\e` stands for the empty key. The root value itself, when it is not
+/// flattened, is stored under the empty key.
+#[derive(Clone, Debug)]
+pub struct FlattenOptions {
+    pub(crate) separator: String,
+    pub(crate) arrays: ArrayIndexStyle,
+    pub(crate) max_depth: Option<usize>,
+}
+
+impl Default for FlattenOptions {
+    fn default() -> Self {
+        FlattenOptions {
+            separator: ".".to_string(),
+            arrays: ArrayIndexStyle::Dot,
+            max_depth: None,
+        }
+    }
+}
+
+impl FlattenOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the separator of the segments of keys, `.` by default.
+    ///
+    /// Flattening and unflattening fail with [`Error::InvalidSeparator`] if the separator is empty,
+    /// contains a backslash, a bracket or a digit, or contains `e` which would make it ambiguous
+    /// with the escaped empty key.
+    pub fn separator(mut self, separator: &str) -> Self {
+        self.separator = separator.to_string();
+        self
+    }
+
+    /// Sets how array indices are written, `ArrayIndexStyle::Dot` by default.
+    pub fn arrays(mut self, arrays: ArrayIndexStyle) -> Self {
+        self.arrays = arrays;
+        self
+    }
+
+    /// Flattens at most `max_depth` levels, deeper objects and arrays being kept as values.
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    fn check_separator(&self) -> Result<()> {
+        let sep = &self.separator;
+        if sep.is_empty() || sep.contains(|c: char| matches!(c, 'e' | '\\' | '[' | ']' | '0'..='9')) {
+            return Err(Error::InvalidSeparator { separator: sep.clone() });
+        }
+        Ok(())
+    }
+}
+
+/// Segment of a flattened key
+#[derive(Debug, PartialEq, Eq)]
+enum Token {
+    Key(String),
+    Index(usize),
+}
+
+/// Flatten a value into an object of leaf values
+pub(crate) fn flatten(val: &Value, options: &FlattenOptions) -> Result<Value> {
+    options.check_separator()?;
+    let mut res = Map::new();
+    flatten_into(val, "", 0, options, &mut res);
+    Ok(Value::Object(res))
+}
+
+fn flatten_into(val: &Value, prefix: &str, depth: usize, options: &FlattenOptions, res: &mut Map<String, Value>) {
+    let deeper = options.max_depth.map_or(true, |max| depth < max);
+    match val {
+        Value::Object(obj) if deeper && !obj.is_empty() => {
+            for (k, v) in obj {
+                let key = if prefix.is_empty() {
+                    escape(k, options)
+                } else {
+                    format!("{}{}{}", prefix, options.separator, escape(k, options))
+                };
+                flatten_into(v, &key, depth + 1, options, res);
+            }
+        }
+        Value::Array(arr) if deeper && !arr.is_empty() => {
+            for (i, v) in arr.iter().enumerate() {
+                let key = match options.arrays {
+                    ArrayIndexStyle::Dot if prefix.is_empty() => i.to_string(),
+                    ArrayIndexStyle::Dot => format!("{}{}{}", prefix, options.separator, i),
+                    ArrayIndexStyle::Brackets => format!("{}[{}]", prefix, i),
+                };
+                flatten_into(v, &key, depth + 1, options, res);
+            }
+        }
+        _ => {
+            res.insert(prefix.to_string(), val.clone());
+        }
+    }
+}
+
+/// Escape an object key so it forms a single segment
+fn escape(key: &str, options: &FlattenOptions) -> String {
+    if key.is_empty() {
+        return "\\e".to_string();
+    }
+    let mut res = String::with_capacity(key.len());
+    if options.arrays == ArrayIndexStyle::Dot && key.bytes().all(|b| b.is_ascii_digit()) {
+        res.push('\\');
+    }
+    for c in key.chars() {
+        // escaping every character of the separator, rather than whole separators, keeps
+        // a key ending with a part of a longer separator from merging with the next one
+        match c {
+            '\\' => res.push('\\'),
+            '[' | ']' if options.arrays == ArrayIndexStyle::Brackets => res.push('\\'),
+            _ if options.separator.contains(c) => res.push('\\'),
+            _ => {}
+        }
+        res.push(c);
+    }
+    res
+}
+
+/// Split a flattened key into its segments, `None` if it is malformed
+fn parse(key: &str, options: &FlattenOptions) -> Option<Vec<Token>> {
+    let sep = options.separator.as_str();
+    let brackets = options.arrays == ArrayIndexStyle::Brackets;
+    let mut tokens = vec![];
+    if key.is_empty() {
+        return Some(tokens);
+    }
+    let mut segment = String::new();
+    // whether the segment had escapes, so that it is a key even if empty or made of digits
+    let mut escaped = false;
+    // whether the segment so far is a bracketed index
+    let mut after_index = false;
+    let mut rest = key;
+    loop {
+        if rest.is_empty() || rest.starts_with(sep) {
+            if !after_index {
+                tokens.push(match segment.as_str() {
+                    "" if !escaped => return None,
+                    s if !escaped && !brackets && s.bytes().all(|b| b.is_ascii_digit()) => Token::Index(s.parse().ok()?),
+                    _ => Token::Key(std::mem::take(&mut segment)),
+                });
+            }
+            if rest.is_empty() {
+                return Some(tokens);
+            }
+            rest = &rest[sep.len()..];
+            segment.clear();
+            escaped = false;
+            after_index = false;
+            continue;
+        }
+        let c = rest.chars().next()?;
+        rest = &rest[c.len_utf8()..];
+        match c {
+            _ if after_index && c != '[' => return None,
+            '\\' => {
+                escaped = true;
+                let e = rest.chars().next()?;
+                rest = &rest[e.len_utf8()..];
+                let ends = rest.is_empty() || rest.starts_with(sep) || (brackets && rest.starts_with('['));
+                if !(e == 'e' && segment.is_empty() && ends) {
+                    segment.push(e);
+                }
+            }
+            '[' if brackets => {
+                if !segment.is_empty() || escaped {
+                    tokens.push(Token::Key(std::mem::take(&mut segment)));
+                } else if !tokens.is_empty() && !after_index {
+                    return None;
+                }
+                let end = rest.find(']')?;
+                let digits = &rest[..end];
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                tokens.push(Token::Index(digits.parse().ok()?));
+                rest = &rest[end + 1..];
+                escaped = false;
+                after_index = true;
+            }
+            ']' if brackets => return None,
+            _ => segment.push(c),
+        }
+    }
+}
+
+/// Rebuild a value from an object of flattened keys
+pub(crate) fn unflatten(val: &Value, options: &FlattenOptions) -> Result<Value> {
+    options.check_separator()?;
+    let flat = match val {
+        Value::Object(obj) => obj,
+        other => {
+            return Err(Error::ShapeMismatch {
+                path: "".to_string(),
+                expected: "object",
+                found: pointer::kind(other),
+            })
+        }
+    };
+    if flat.is_empty() {
+        return Ok(Value::Object(Map::new()));
+    }
+    let mut root = Value::Null;
+    let mut owners = HashMap::new();
+    for (k, v) in flat {
+        let tokens = parse(k, options).ok_or_else(|| Error::InvalidKey { key: k.clone() })?;
+        insert(&mut root, &tokens, v.clone(), "", k, &mut owners)?;
+    }
+    Ok(root)
+}
+
+/// Flattened keys that created the nodes, by JSON pointer, and whether the nodes are leaves
+type Owners<'a> = HashMap<String, (&'a str, bool)>;
+
+/// Set the value at the given segments, creating objects and arrays on the way. Fails if a node
+/// would be both a leaf and a container, or set by two keys.
+fn insert<'a>(node: &mut Value, tokens: &[Token], value: Value, path: &str, key: &'a str, owners: &mut Owners<'a>) -> Result<()> {
+    match owners.get(path) {
+        Some(&(first, leaf)) if leaf || tokens.is_empty() => {
+            return Err(Error::KeyCollision {
+                path: path.to_string(),
+                first: first.to_string(),
+                second: key.to_string(),
+            })
+        }
+        Some(_) => {}
+        None => {
+            owners.insert(path.to_string(), (key, tokens.is_empty()));
+        }
+    }
+    let (token, rest) = match tokens.split_first() {
+        Some(t) => t,
+        None => {
+            *node = value;
+            return Ok(());
+        }
+    };
+    match token {
+        Token::Key(k) => {
+            if node.is_null() {
+                *node = Value::Object(Map::new());
+            }
+            match node {
+                Value::Object(obj) => insert(
+                    obj.entry(k.clone()).or_insert(Value::Null),
+                    rest,
+                    value,
+                    &pointer::push(path, k),
+                    key,
+                    owners,
+                ),
+                other => Err(Error::ShapeMismatch {
+                    path: path.to_string(),
+                    expected: "object",
+                    found: pointer::kind(other),
+                }),
+            }
+        }
+        Token::Index(i) => {
+            if node.is_null() {
+                *node = Value::Array(vec![]);
+            }
+            match node {
+                Value::Array(arr) => {
+                    if *i > arr.len() + MAX_PADDING {
+                        return Err(Error::LimitExceeded {
+                            path: pointer::push(path, &i.to_string()),
+                            what: "array padding",
+                            limit: MAX_PADDING,
+                        });
+                    }
+                    if arr.len() <= *i {
+                        arr.resize(i + 1, Value::Null);
+                    }
+                    insert(&mut arr[*i], rest, value, &pointer::push(path, &i.to_string()), key, owners)
+                }
+                other => Err(Error::ShapeMismatch {
+                    path: path.to_string(),
+                    expected: "array",
+                    found: pointer::kind(other),
+                }),
+            }
+        }
+    }
+}
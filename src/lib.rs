@@ -1,11 +1,40 @@
-use crate::utils::{DedupeHashValue, HashValue};
 use serde_json::Value::{self, *};
 use serde::de::DeserializeOwned;
 
 #[cfg(test)]
 mod tests;
+mod canonical;
+mod casefold;
+mod dedup;
+mod diff;
+mod error;
+mod fingerprint;
+mod flatten;
+mod merge;
+pub mod patch;
+mod pointer;
+mod prune;
+mod query;
+mod remap;
+mod rename;
 mod utils;
 
+pub use dedup::{ArrayCompare, DedupOptions, DedupReport, DedupScope, Keep, Kept, NumberCompare, StringCompare};
+pub use diff::{DiffOptions, DiffReport, Difference};
+pub use error::{Error, Result};
+pub use fingerprint::{Fingerprint, FingerprintOptions};
+pub use flatten::{ArrayIndexStyle, FlattenOptions};
+pub use merge::{
+    create_merge_patch, ArrayMerge, Conflict, MergeOptions, MergeSimilarOptions, Placement,
+};
+pub use prune::{PruneOptions, Removed};
+pub use query::QueryMatch;
+pub use remap::Mapping;
+pub use rename::{Case, RenameOptions};
+
+/// Maximum nesting depth accepted by the `try_*` operations, same as the recursion limit of `serde_json`.
+pub const MAX_DEPTH: usize = 128;
+
 pub trait JsonUtils {
     /// Remove `Null` value fields from serde_json::Value
     /// ## Example
@@ -35,6 +64,58 @@ pub trait JsonUtils {
     /// ```
     fn skip_null_and_empty(&mut self);
 
+    /// Remove the fields & elements selected by the options from serde_json::Value
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{JsonUtils, PruneOptions};
+    ///
+    /// let mut x = json!({"a": {"b": null, "c": " "}, "d": [0, false, "foo"]});
+    /// x.prune_with(&PruneOptions::new().blank_strings(true).falses(true).zeros(true).empty_objects(true).cascade(true));
+    /// assert_eq!(x, json!({"d": ["foo"]}));
+    /// ```
+    fn prune_with(&mut self, options: &PruneOptions);
+
+    /// Remove the fields & elements for which `predicate` returns true. The predicate receives the
+    /// JSON pointer and the value of every field & element, before the ones they contain.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::JsonUtils;
+    ///
+    /// let mut x = json!({"key1": "N/A", "key2": ["foo", "-"], "key3": {"key4": "-"}});
+    /// x.prune(|_path, v| v == "N/A" || v == "-");
+    /// assert_eq!(x, json!({"key2": ["foo"], "key3": {}}));
+    ///
+    /// let mut x = json!({"key1": "N/A", "key2": {"key1": "N/A"}});
+    /// x.prune(|path, _v| path == "/key2/key1");
+    /// assert_eq!(x, json!({"key1": "N/A", "key2": {}}));
+    /// ```
+    fn prune<F: FnMut(&str, &Value) -> bool>(&mut self, predicate: F);
+
+    /// Same as `skip_null`, returning the removed fields & elements, with their values if `with_values` is set.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{JsonUtils, Removed};
+    ///
+    /// let mut x = json!({"key1": null, "key2": ["foo", null]});
+    /// let removed = x.skip_null_with_report(false);
+    /// assert_eq!(x, json!({"key2": ["foo"]}));
+    /// assert_eq!(removed, vec![
+    ///     Removed { path: "/key1".to_string(), value: None },
+    ///     Removed { path: "/key2/1".to_string(), value: None },
+    /// ]);
+    /// ```
+    fn skip_null_with_report(&mut self, with_values: bool) -> Vec<Removed>;
+
+    /// Same as `skip_null_and_empty`, returning the removed fields & elements, with their values if `with_values` is set.
+    fn skip_null_and_empty_with_report(&mut self, with_values: bool) -> Vec<Removed>;
+
+    /// Same as `prune_with`, returning the removed fields & elements, with their values if `with_values` is set.
+    /// When the options cascade, the fields & elements removed from a value are reported before the value itself.
+    fn prune_with_report(&mut self, options: &PruneOptions, with_values: bool) -> Vec<Removed>;
+
     /// `Dedup` array of json's from serde_json::Value
     /// ## Example
     /// ```rust
@@ -48,9 +129,84 @@ pub trait JsonUtils {
     /// assert_eq!(x, x_result);
     /// ```
     fn dedup(&mut self);
-    
+
+    /// Same as `dedup`, with the given options controlling how values are compared.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{DedupOptions, JsonUtils, StringCompare};
+    ///
+    /// let mut x = json!(["Foo", "foo", " FOO ", "bar"]);
+    /// x.dedup_with(&DedupOptions::new().strings(StringCompare::CaseInsensitive).normalize_whitespace(true));
+    /// assert_eq!(x, json!(["Foo", "bar"]));
+    /// ```
+    fn dedup_with(&mut self, options: &DedupOptions);
+
+    /// Same as `dedup_with`, returning for every deduplicated array the elements kept
+    /// along with the indices of their removed duplicates.
+    /// Inner arrays are deduplicated first, and come first in the report.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{DedupOptions, JsonUtils};
+    ///
+    /// let mut x = json!({"feed": ["a", "b", "a", "a"]});
+    /// let report = x.dedup_with_counts(&DedupOptions::new());
+    /// assert_eq!(x, json!({"feed": ["a", "b"]}));
+    /// assert_eq!(report[0].path, "/feed");
+    /// assert_eq!(report[0].kept[0].index, 0);
+    /// assert_eq!(report[0].kept[0].duplicates, vec![2, 3]);
+    /// assert_eq!(report[0].kept[0].count(), 3);
+    /// ```
+    fn dedup_with_counts(&mut self, options: &DedupOptions) -> Vec<DedupReport>;
+
+    /// `Dedup` an array, treating as duplicates the elements having equal values at all the
+    /// given JSON pointers. Elements missing one of the pointers are always kept, and the array
+    /// is left unchanged when no pointers are given.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{JsonUtils, Keep};
+    ///
+    /// let mut x = json!([
+    ///     {"id": 1, "source": {"host": "a"}, "ts": 10},
+    ///     {"id": 2, "source": {"host": "a"}, "ts": 11},
+    ///     {"id": 1, "source": {"host": "a"}, "ts": 12}
+    /// ]);
+    /// x.dedup_by_keys(&["/id", "/source/host"], Keep::Merge);
+    /// assert_eq!(x, json!([
+    ///     {"id": 1, "source": {"host": "a"}, "ts": [10, 12]},
+    ///     {"id": 2, "source": {"host": "a"}, "ts": 11}
+    /// ]));
+    /// ```
+    fn dedup_by_keys(&mut self, keys: &[&str], keep: Keep);
+
+    /// Merge elements of arrays that are similar: objects having the same keys are merged into one
+    /// object whose differing values are collected into arrays, and other equal elements are kept once.
+    /// Each group of merged elements keeps the position of its first element.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::JsonUtils;
+    ///
+    /// let mut x = json!([{"key1": "foo", "key2": "bar"}, 1, {"key1": "foo", "key2": "baz"}, 1]);
+    /// x.merge_similar();
+    /// assert_eq!(x, json!([{"key1": "foo", "key2": ["bar", "baz"]}, 1]));
+    /// ```
     fn merge_similar(&mut self);
 
+    /// Same as `merge_similar`, with the given options.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{JsonUtils, MergeSimilarOptions, Placement};
+    ///
+    /// let mut x = json!([{"key1": "foo", "key2": "bar"}, 1, {"key1": "foo", "key2": "baz"}]);
+    /// x.merge_similar_with(&MergeSimilarOptions::new().placement(Placement::Last));
+    /// assert_eq!(x, json!([1, {"key1": "foo", "key2": ["bar", "baz"]}]));
+    /// ```
+    fn merge_similar_with(&mut self, options: &MergeSimilarOptions);
+
     /// Converts a Value to a Struct of the provided type. The provided struct must implement the `serde::Deserialize` trait.
     /// ## Usage
     /// ```rust
@@ -94,6 +250,340 @@ pub trait JsonUtils {
     /// assert_eq!(x, x_result);
     /// ```
     fn extend(&mut self, value: Value);
+
+    /// Recursively merges `value` into this value: objects are merged key by key, arrays
+    /// are combined and conflicting values resolved according to the options.
+    /// On failure the value is left unchanged.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{ArrayMerge, Conflict, JsonUtils, MergeOptions};
+    ///
+    /// let mut x = json!({"user": {"name": "foo", "tags": ["a"], "roles": [{"id": 1, "level": 1}]}});
+    /// let options = MergeOptions::new()
+    ///     .arrays(ArrayMerge::Union)
+    ///     .arrays_at("/user/roles", ArrayMerge::ByKey("/id".to_string()))
+    ///     .conflicts(Conflict::Collect);
+    /// x.deep_merge(json!({"user": {"name": "bar", "tags": ["a", "b"], "roles": [{"id": 1, "level": 2}]}}), &options).unwrap();
+    /// assert_eq!(x, json!({"user": {"name": ["foo", "bar"], "tags": ["a", "b"], "roles": [{"id": 1, "level": [1, 2]}]}}));
+    /// ```
+    fn deep_merge(&mut self, value: Value, options: &MergeOptions) -> Result<()>;
+
+    /// Applies a JSON Merge Patch (RFC 7396): `null` members of the patch remove the member,
+    /// objects are merged recursively and any other value replaces the existing one.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::JsonUtils;
+    ///
+    /// let mut x = json!({"a": "b", "c": {"d": "e", "f": "g"}});
+    /// x.merge_patch(&json!({"a": "z", "c": {"f": null}}));
+    /// assert_eq!(x, json!({"a": "z", "c": {"d": "e"}}));
+    /// ```
+    fn merge_patch(&mut self, patch: &Value);
+
+    /// Applies the operations of a JSON Patch (RFC 6902) in order. If any of them fails
+    /// the value is left unchanged. See the [`patch`] module.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{patch::PatchOperation, Error, JsonUtils};
+    ///
+    /// let mut x = json!({"foo": ["bar", "baz"]});
+    /// let patch = vec![
+    ///     PatchOperation::Add { path: "/foo/1".to_string(), value: json!("qux") },
+    ///     PatchOperation::Test { path: "/foo/0".to_string(), value: json!("baz") },
+    /// ];
+    /// assert!(matches!(x.apply_patch(&patch), Err(Error::TestFailed { .. })));
+    /// assert_eq!(x, json!({"foo": ["bar", "baz"]}));
+    /// ```
+    fn apply_patch(&mut self, patch: &[patch::PatchOperation]) -> Result<()>;
+
+    /// Lists the differences between this value and `other`, with their JSON pointers.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{Difference, JsonUtils};
+    ///
+    /// let diff = json!({"key1": "foo", "key2": [1, 2]}).diff(&json!({"key1": "bar", "key2": [1]}));
+    /// assert_eq!(diff, vec![
+    ///     Difference::Changed { path: "/key1".to_string(), from: json!("foo"), to: json!("bar") },
+    ///     Difference::Removed { path: "/key2/1".to_string(), value: json!(2) },
+    /// ]);
+    /// ```
+    fn diff(&self, other: &Value) -> Vec<Difference>;
+
+    /// Same as `diff`, with the given options.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{DiffOptions, JsonUtils};
+    ///
+    /// let options = DiffOptions::new().ignore_array_order(true).ignore("/**/ts").epsilon(0.01);
+    /// let diff = json!({"tags": ["a", "b"], "ts": 1, "score": 0.5}).diff_with(&json!({"tags": ["b", "a"], "ts": 2, "score": 0.501}), &options);
+    /// assert!(diff.is_empty());
+    /// ```
+    fn diff_with(&self, other: &Value, options: &DiffOptions) -> Vec<Difference>;
+
+    /// Sets the value at a JSON pointer and returns the value it replaced. Missing objects and
+    /// arrays on the way are created, an array when indexed by `-` or a number. In arrays, `-`
+    /// and the length of the array append the value. The value is left unchanged on failure.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{Error, JsonUtils};
+    ///
+    /// let mut x = json!({"key1": "foo"});
+    /// x.set_path("/a/b/0/c", json!(1)).unwrap();
+    /// x.set_path("/a/b/-", json!(2)).unwrap();
+    /// assert_eq!(x, json!({"key1": "foo", "a": {"b": [{"c": 1}, 2]}}));
+    /// assert_eq!(x.set_path("/key1", json!("bar")).unwrap(), Some(json!("foo")));
+    /// assert!(matches!(x.set_path("/key1/c", json!(1)), Err(Error::ShapeMismatch { .. })));
+    /// ```
+    fn set_path(&mut self, path: &str, value: Value) -> Result<Option<Value>>;
+
+    /// Inserts a value at a JSON pointer, shifting the following elements of arrays, where `-`
+    /// appends the value. Missing objects and arrays on the way are created like in `set_path`,
+    /// and the value is left unchanged on failure.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::JsonUtils;
+    ///
+    /// let mut x = json!({"items": [1, 3]});
+    /// x.insert_path("/items/1", json!(2)).unwrap();
+    /// x.insert_path("/items/-", json!(4)).unwrap();
+    /// assert_eq!(x, json!({"items": [1, 2, 3, 4]}));
+    /// ```
+    fn insert_path(&mut self, path: &str, value: Value) -> Result<()>;
+
+    /// Removes the value at a JSON pointer from its object or array and returns it.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{Error, JsonUtils};
+    ///
+    /// let mut x = json!({"items": [1, 2]});
+    /// assert_eq!(x.remove_path("/items/0").unwrap(), json!(1));
+    /// assert_eq!(x, json!({"items": [2]}));
+    /// assert!(matches!(x.remove_path("/other"), Err(Error::PathNotFound { .. })));
+    /// ```
+    fn remove_path(&mut self, path: &str) -> Result<Value>;
+
+    /// Takes the value at a JSON pointer, leaving `null` in its place.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::JsonUtils;
+    ///
+    /// let mut x = json!({"items": [1, 2]});
+    /// assert_eq!(x.take_path("/items/0").unwrap(), json!(1));
+    /// assert_eq!(x, json!({"items": [null, 2]}));
+    /// ```
+    fn take_path(&mut self, path: &str) -> Result<Value>;
+
+    /// Selects nodes with a JSONPath query (RFC 9535) and returns them with their normalized paths,
+    /// in the order of the query. Filters, slices, recursive descent and the `length`, `count`,
+    /// `match`, `search` and `value` functions are supported, `match` and `search` only with the
+    /// `regex` feature (enabled by default). Queries nesting filters, parentheses and function calls
+    /// deeper than [`MAX_DEPTH`] are rejected as invalid.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::JsonUtils;
+    ///
+    /// let x = json!({"items": [{"model": "A", "year": 2017}, {"model": "B", "year": 2020}]});
+    /// let found = x.query("$.items[?(@.year > 2018)].model").unwrap();
+    /// assert_eq!(found.len(), 1);
+    /// assert_eq!(found[0].path, "$['items'][1]['model']");
+    /// assert_eq!(found[0].value, &json!("B"));
+    /// ```
+    fn query(&self, path: &str) -> Result<Vec<QueryMatch<'_>>>;
+
+    /// Calls `f` with the normalized path and the value of every node selected by a JSONPath
+    /// query, and returns the number of nodes. Nodes are visited deepest first, so `f` may change
+    /// the structure of the value it gets: the nodes it contains have already been visited.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::JsonUtils;
+    ///
+    /// let mut x = json!({"items": [{"price": 10}, {"price": 25}]});
+    /// let n = x.query_mut("$.items[?@.price < 20].price", |_, v| *v = json!(20)).unwrap();
+    /// assert_eq!(n, 1);
+    /// assert_eq!(x, json!({"items": [{"price": 20}, {"price": 25}]}));
+    /// ```
+    fn query_mut<F: FnMut(&str, &mut Value)>(&mut self, path: &str, f: F) -> Result<usize>;
+
+    /// Flattens nested objects and arrays into an object mapping keys such as `a.b.0.c` to
+    /// leaf values. Empty objects and arrays are kept as leaf values, so that `unflatten`
+    /// always rebuilds the original value. Fails with [`Error::InvalidSeparator`] if the
+    /// separator of the options is not allowed.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{ArrayIndexStyle, FlattenOptions, JsonUtils};
+    ///
+    /// let x = json!({"a": {"b": [{"c": 1}, 2]}, "d.e": []});
+    /// let flat = x.flatten(&FlattenOptions::new()).unwrap();
+    /// assert_eq!(flat, json!({"a.b.0.c": 1, "a.b.1": 2, "d\\.e": []}));
+    /// assert_eq!(flat.unflatten(&FlattenOptions::new()).unwrap(), x);
+    ///
+    /// let options = FlattenOptions::new().arrays(ArrayIndexStyle::Brackets).separator("/");
+    /// assert_eq!(x.flatten(&options).unwrap(), json!({"a/b[0]/c": 1, "a/b[1]": 2, "d.e": []}));
+    /// ```
+    fn flatten(&self, options: &FlattenOptions) -> Result<Value>;
+
+    /// Rebuilds the value flattened by `flatten` with the same options. Missing array elements
+    /// are `null`, up to 65536 of them before an index. Fails with [`Error::KeyCollision`] if
+    /// two keys set the same value, or one sets a value inside the other's, as `a` and `a.b` do.
+    fn unflatten(&self, options: &FlattenOptions) -> Result<Value>;
+
+    /// Recursively renames the keys of objects to the given case. The value is left unchanged
+    /// and [`Error::KeyCollision`] is returned if two keys of an object get the same name.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{Case, JsonUtils};
+    ///
+    /// let mut x = json!({"userName": "foo", "Address": {"zip-code": 1}, "items": [{"HTTPStatus": 200}]});
+    /// x.rename_keys(Case::Snake).unwrap();
+    /// assert_eq!(x, json!({"user_name": "foo", "address": {"zip_code": 1}, "items": [{"http_status": 200}]}));
+    /// ```
+    fn rename_keys(&mut self, case: Case) -> Result<()>;
+
+    /// Same as `rename_keys`, but with options.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{Case, JsonUtils, RenameOptions};
+    ///
+    /// let mut x = json!({"user_name": "foo", "labels": {"app_name": "bar"}});
+    /// x.rename_keys_with(&RenameOptions::new().case(Case::Camel).skip("/labels")).unwrap();
+    /// assert_eq!(x, json!({"userName": "foo", "labels": {"app_name": "bar"}}));
+    /// ```
+    fn rename_keys_with(&mut self, options: &RenameOptions) -> Result<()>;
+
+    /// Moves values between JSON pointers and returns the source pointers that were not found,
+    /// in which the `*` tokens are replaced by the keys and indices matched before the missing
+    /// value. The value is left unchanged if a move fails.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{JsonUtils, Mapping};
+    ///
+    /// let mut x = json!({"user": {"name": "foo"}, "ts": 1, "items": [{"id": 1}, {"id": 2}]});
+    /// let mapping = Mapping::new()
+    ///     .map("/user/name", "/fullName")
+    ///     .map("/ts", "/meta/timestamp")
+    ///     .map("/items/*/id", "/items/*/identifier")
+    ///     .map("/missing", "/other");
+    /// assert_eq!(x.remap(&mapping).unwrap(), vec!["/missing".to_string()]);
+    /// assert_eq!(x, json!({
+    ///     "user": {}, "fullName": "foo", "meta": {"timestamp": 1},
+    ///     "items": [{"identifier": 1}, {"identifier": 2}],
+    /// }));
+    /// ```
+    fn remap(&mut self, mapping: &Mapping) -> Result<Vec<std::string::String>>;
+
+    /// Serializes the value with the JSON Canonicalization Scheme (RFC 8785), so that equal
+    /// values always give the same bytes: object keys are sorted by their UTF-16 code units,
+    /// numbers are formatted like in ECMAScript and strings are escaped minimally. Numbers are
+    /// written as IEEE 754 doubles, so integers above 2^53 may lose precision.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::JsonUtils;
+    ///
+    /// let x = json!({"b": [1.0, 1e21, 0.000001], "a": "\u{e9}\n", "\u{20ac}": null});
+    /// assert_eq!(x.to_canonical_string(), r#"{"a":"é\n","b":[1,1e+21,0.000001],"€":null}"#);
+    /// ```
+    fn to_canonical_string(&self) -> std::string::String;
+
+    /// Writes the value with the JSON Canonicalization Scheme (RFC 8785), like `to_canonical_string`.
+    fn write_canonical<W: std::io::Write>(&self, writer: W) -> std::io::Result<()>;
+
+    /// Computes a stable SHA-256 based digest of the value, independent of the order of object keys.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::JsonUtils;
+    ///
+    /// let x = json!({"a": 1, "b": [true, null]});
+    /// let y = json!({"b": [true, null], "a": 1});
+    /// assert_eq!(x.fingerprint(), y.fingerprint());
+    /// assert_ne!(x.fingerprint(), json!({"a": 1.0, "b": [true, null]}).fingerprint());
+    /// ```
+    fn fingerprint(&self) -> Fingerprint;
+
+    /// Same as `fingerprint`, but values equal according to the string, number and array comparisons of
+    /// the options get the same fingerprint.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{FingerprintOptions, JsonUtils, NumberCompare, StringCompare};
+    ///
+    /// let options = FingerprintOptions::new().strings(StringCompare::CaseInsensitive).numbers(NumberCompare::Semantic);
+    /// let x = json!({"name": "Foo", "size": 1});
+    /// let y = json!({"name": "FOO", "size": 1.0});
+    /// assert_eq!(x.fingerprint_with(&options), y.fingerprint_with(&options));
+    /// assert_eq!(x.fingerprint_with(&options).to_hex().len(), 64);
+    /// ```
+    fn fingerprint_with(&self, options: &FingerprintOptions) -> Fingerprint;
+
+    /// Same as `skip_null`, but fails instead of recursing deeper than [`MAX_DEPTH`].
+    fn try_skip_null(&mut self) -> Result<()>;
+
+    /// Same as `skip_null_and_empty`, but fails instead of recursing deeper than [`MAX_DEPTH`].
+    fn try_skip_null_and_empty(&mut self) -> Result<()>;
+
+    /// Same as `dedup`, but fails instead of recursing deeper than [`MAX_DEPTH`].
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{Error, JsonUtils, MAX_DEPTH};
+    ///
+    /// let mut x = json!([]);
+    /// for _ in 0..MAX_DEPTH {
+    ///     x = json!([x]);
+    /// }
+    /// assert!(matches!(x.try_dedup(), Err(Error::LimitExceeded { .. })));
+    /// ```
+    fn try_dedup(&mut self) -> Result<()>;
+
+    /// Same as `merge_similar`, but fails instead of recursing deeper than [`MAX_DEPTH`].
+    fn try_merge_similar(&mut self) -> Result<()>;
+
+    /// Same as `to_struct`, but returns the reason of the failure along with the
+    /// JSON pointer of the field that could not be deserialized.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::JsonUtils;
+    /// use serde::Deserialize;
+    ///
+    /// #[derive(Deserialize, Debug)]
+    /// pub struct Car {
+    ///     model: String,
+    ///     year: i32
+    /// }
+    ///
+    /// let x = json!({"items": [{"model": "a", "year": 2019}, {"model": "b", "year": "2020"}]});
+    /// let err = x.try_to_struct::<std::collections::HashMap<String, Vec<Car>>>().unwrap_err();
+    /// assert_eq!(err.to_string(), "/items/1/year: invalid type: string \"2020\", expected i32");
+    /// ```
+    fn try_to_struct<T: DeserializeOwned>(self) -> Result<T>;
+
+    /// Same as `extend`, but fails when the two values cannot be combined instead of ignoring `value`.
+    /// ## Example
+    /// ```rust
+    /// use serde_json::json;
+    /// use serde_json_utils::{Error, JsonUtils};
+    ///
+    /// let mut x = json!({"key1": "bar"});
+    /// assert!(matches!(x.try_extend(json!("foo")), Err(Error::ShapeMismatch { .. })));
+    /// ```
+    fn try_extend(&mut self, value: Value) -> Result<()>;
 }
 
 
@@ -112,7 +602,7 @@ impl JsonUtils for Value {
     /// assert_eq!(x, x_result);
     /// ```
     fn skip_null(&mut self) {
-        remove_nulls(self, false);
+        self.prune_with(&PruneOptions::default());
     }
 
     /// Remove `Null` value fields & `empty` value fields from serde_json::Value
@@ -128,7 +618,33 @@ impl JsonUtils for Value {
     /// assert_eq!(x, x_result);
     /// ```
     fn skip_null_and_empty(&mut self) {
-        remove_nulls(self, true);
+        self.prune_with(&PruneOptions::null_and_empty());
+    }
+
+    fn prune_with(&mut self, options: &PruneOptions) {
+        prune::prune(self, options, None);
+    }
+
+    fn prune<F: FnMut(&str, &Value) -> bool>(&mut self, predicate: F) {
+        prune::Pruner {
+            predicate,
+            cascade: false,
+            track: true,
+            report: None,
+        }
+        .prune(self, "");
+    }
+
+    fn skip_null_with_report(&mut self, with_values: bool) -> Vec<Removed> {
+        self.prune_with_report(&PruneOptions::default(), with_values)
+    }
+
+    fn skip_null_and_empty_with_report(&mut self, with_values: bool) -> Vec<Removed> {
+        self.prune_with_report(&PruneOptions::null_and_empty(), with_values)
+    }
+
+    fn prune_with_report(&mut self, options: &PruneOptions, with_values: bool) -> Vec<Removed> {
+        prune::prune(self, options, Some(with_values))
     }
 
     /// `Dedup` array of json's from serde_json::Value
@@ -144,62 +660,29 @@ impl JsonUtils for Value {
     /// assert_eq!(x, x_result);
     /// ```
     fn dedup(&mut self) {
-        match self {
-            Null => {}
-            Bool(_) => {}
-            Number(_) => {}
-            String(_) => {}
-            Array(a) => {
-                let mut aa = a.clone();
-                for v in &mut aa {
-                    v.dedup();
-                }
-                let mut set = std::collections::HashSet::new();
-                let mut candidates = vec![];
-                for v in &aa {
-                    if !set.contains(&DedupeHashValue(v)) {
-                        set.insert(DedupeHashValue(v));
-                        candidates.push(v.clone());
-                    }
-                }
-                a.clear();
-                a.extend(candidates);
-            }
-            Object(o) => {
-                for (_, v) in o.iter_mut() {
-                    v.dedup();
-                }
-            }
-        }
+        self.dedup_with(&DedupOptions::default());
     }
-    
-    fn merge_similar(&mut self){
-        match self {
-            Array(arr) => {
-                let mut res: std::collections::HashSet<HashValue> = std::collections::HashSet::new();
-                let aarr = arr.clone();
-                for v in aarr{
-                    if let Some(s) = res.take(&HashValue(v.clone())){
-                        if let Ok(m) = merge_similar_objects(&s.0, &v){
-                            res.insert(HashValue(m));
-                        } else {
-                            res.insert(s);
-                            res.insert(HashValue(v.clone()));
-                        }
-                    } else {
-                        res.insert(HashValue(v.clone()));
-                    }
-                }
-                arr.clear();
-                arr.extend(res.into_iter().map(|s| s.0).collect::<Vec<Value>>());
-            }
-            Object(obj) => {
-                for (_k, v) in obj{
-                    v.merge_similar();
-                }
-            }
-            _ => {}
-        }
+
+    fn dedup_with(&mut self, options: &DedupOptions) {
+        dedup::dedup(self, options, None);
+    }
+
+    fn dedup_with_counts(&mut self, options: &DedupOptions) -> Vec<DedupReport> {
+        let mut report = vec![];
+        dedup::dedup(self, options, Some(&mut report));
+        report
+    }
+
+    fn dedup_by_keys(&mut self, keys: &[&str], keep: Keep) {
+        dedup::dedup_by_keys(self, keys, keep);
+    }
+
+    fn merge_similar(&mut self) {
+        self.merge_similar_with(&MergeSimilarOptions::default());
+    }
+
+    fn merge_similar_with(&mut self, options: &MergeSimilarOptions) {
+        merge::merge_similar(self, options);
     }
 
     /// Converts a Value to a Struct of the provided type. The provided struct must implement the `serde::Deserialize` trait.
@@ -229,14 +712,8 @@ impl JsonUtils for Value {
     /// }
     /// ```
     fn to_struct< T: DeserializeOwned>(self) -> Option<T> {
-        let cast_type: Option<T> = match serde_json::from_value(self) { 
-            Ok(t) => Some(t),
-            _=> {
-                // Returns none if the value could not be properly parsed.
-                None
-            }
-        };
-        cast_type
+        // Returns none if the value could not be properly parsed.
+        self.try_to_struct().ok()
     }
 
     /// Extends a value with another value. If a value contains values with the same keys, the values are combined.
@@ -281,81 +758,189 @@ impl JsonUtils for Value {
             _=> {}
         };
     }
-}
 
+    fn deep_merge(&mut self, value: Value, options: &MergeOptions) -> Result<()> {
+        let mut merged = self.clone();
+        merge::deep_merge(&mut merged, value, options, "")?;
+        *self = merged;
+        Ok(())
+    }
 
+    fn merge_patch(&mut self, patch: &Value) {
+        merge::merge_patch(self, patch);
+    }
 
-/// Remove `Null` value fields & `empty` value fields from serde_json::Value
-fn remove_nulls(val: &mut Value, with_empties: bool) -> bool {
-    match val {
-        Null => {
-            return true;
-        }
-        Bool(_) => {}
-        Number(_) => {}
-        String(_) => {}
-        Array(arr) => {
-            if with_empties && arr.is_empty() {
-                return true;
-            }
-            let mut candidates = vec![];
-            for v in &mut arr.clone() {
-                if !remove_nulls(v, with_empties) {
-                    candidates.push(v.clone());
-                }
-            }
-            arr.clear();
-            arr.extend(candidates);
-        }
-        Object(obj) => {
-            if with_empties && obj.is_empty() {
-                return true;
-            }
-            let mut candidates = vec![];
-            for (k, v) in obj.iter_mut() {
-                if remove_nulls(v, with_empties) {
-                    candidates.push(k.clone());
-                }
-            }
-            for c in candidates {
-                obj.remove(&c);
+    fn apply_patch(&mut self, patch: &[patch::PatchOperation]) -> Result<()> {
+        patch::apply_patch(self, patch)
+    }
+
+    fn diff(&self, other: &Value) -> Vec<Difference> {
+        self.diff_with(other, &DiffOptions::default())
+    }
+
+    fn diff_with(&self, other: &Value, options: &DiffOptions) -> Vec<Difference> {
+        diff::diff(self, other, options)
+    }
+
+    fn set_path(&mut self, path: &str, value: Value) -> Result<Option<Value>> {
+        pointer::set(self, path, value, false)
+    }
+
+    fn insert_path(&mut self, path: &str, value: Value) -> Result<()> {
+        pointer::set(self, path, value, true).map(|_| ())
+    }
+
+    fn remove_path(&mut self, path: &str) -> Result<Value> {
+        pointer::remove(self, path)
+    }
+
+    fn take_path(&mut self, path: &str) -> Result<Value> {
+        let tokens = pointer::parse(path)?;
+        pointer::resolve_mut(self, &tokens, path).map(std::mem::take)
+    }
+
+    fn flatten(&self, options: &FlattenOptions) -> Result<Value> {
+        flatten::flatten(self, options)
+    }
+
+    fn unflatten(&self, options: &FlattenOptions) -> Result<Value> {
+        flatten::unflatten(self, options)
+    }
+
+    fn rename_keys(&mut self, case: Case) -> Result<()> {
+        self.rename_keys_with(&RenameOptions::new().case(case))
+    }
+
+    fn rename_keys_with(&mut self, options: &RenameOptions) -> Result<()> {
+        let mut renamed = self.clone();
+        rename::rename_keys(&mut renamed, options, "")?;
+        *self = renamed;
+        Ok(())
+    }
+
+    fn remap(&mut self, mapping: &Mapping) -> Result<Vec<std::string::String>> {
+        let mut remapped = self.clone();
+        let unmatched = remap::remap(&mut remapped, mapping)?;
+        *self = remapped;
+        Ok(unmatched)
+    }
+
+    fn to_canonical_string(&self) -> std::string::String {
+        let mut buf = vec![];
+        canonical::write(self, &mut buf).unwrap();
+        std::string::String::from_utf8(buf).unwrap()
+    }
+
+    fn write_canonical<W: std::io::Write>(&self, mut writer: W) -> std::io::Result<()> {
+        canonical::write(self, &mut writer)
+    }
+
+    fn fingerprint(&self) -> Fingerprint {
+        self.fingerprint_with(&FingerprintOptions::new())
+    }
+
+    fn fingerprint_with(&self, options: &FingerprintOptions) -> Fingerprint {
+        fingerprint::fingerprint(self, &options.compare)
+    }
+
+    fn query(&self, path: &str) -> Result<Vec<QueryMatch<'_>>> {
+        query::query(self, path)
+    }
+
+    fn query_mut<F: FnMut(&str, &mut Value)>(&mut self, path: &str, f: F) -> Result<usize> {
+        query::query_mut(self, path, f)
+    }
+
+    fn try_skip_null(&mut self) -> Result<()> {
+        check_depth(self, 0, "")?;
+        self.skip_null();
+        Ok(())
+    }
+
+    fn try_skip_null_and_empty(&mut self) -> Result<()> {
+        check_depth(self, 0, "")?;
+        self.skip_null_and_empty();
+        Ok(())
+    }
+
+    fn try_dedup(&mut self) -> Result<()> {
+        check_depth(self, 0, "")?;
+        self.dedup();
+        Ok(())
+    }
+
+    fn try_merge_similar(&mut self) -> Result<()> {
+        check_depth(self, 0, "")?;
+        self.merge_similar();
+        Ok(())
+    }
+
+    fn try_to_struct<T: DeserializeOwned>(self) -> Result<T> {
+        serde_path_to_error::deserialize(self).map_err(|e| Error::Deserialize {
+            path: deserialize_path(e.path()),
+            message: e.inner().to_string(),
+        })
+    }
+
+    fn try_extend(&mut self, value: Value) -> Result<()> {
+        match (&*self, &value) {
+            (Object(_), Object(_)) | (Object(_), Array(_)) | (Array(_), Object(_)) | (Array(_), Array(_)) => {
+                self.extend(value);
+                Ok(())
             }
+            (Object(_), _) | (Array(_), _) => Err(Error::ShapeMismatch {
+                path: "".to_string(),
+                expected: "object or array",
+                found: pointer::kind(&value),
+            }),
+            _ => Err(Error::ShapeMismatch {
+                path: "".to_string(),
+                expected: "object or array",
+                found: pointer::kind(self),
+            }),
         }
     }
-    false
 }
 
-/// merge similar objects
-fn merge_similar_objects(p: &Value, v: &Value) -> Result<Value, ()> {
-    match (p, v) {
-        (Object(a), Object(b)) => {
-            if HashValue(p.clone()) != HashValue(v.clone()) {
-                return Err(());
+
+
+/// Converts the path tracked during deserialization to a JSON pointer
+fn deserialize_path(path: &serde_path_to_error::Path) -> std::string::String {
+    use serde_path_to_error::Segment;
+
+    let mut res = "".to_string();
+    for segment in path.iter() {
+        res = match segment {
+            Segment::Seq { index } => pointer::push(&res, &index.to_string()),
+            Segment::Map { key } => pointer::push(&res, key),
+            Segment::Enum { variant } => pointer::push(&res, variant),
+            Segment::Unknown => res,
+        };
+    }
+    res
+}
+
+/// Fails when the nesting depth of `val` exceeds [`MAX_DEPTH`]
+fn check_depth(val: &Value, depth: usize, path: &str) -> Result<()> {
+    if matches!(val, Array(_) | Object(_)) && depth >= MAX_DEPTH {
+        return Err(Error::LimitExceeded {
+            path: path.to_string(),
+            what: "depth",
+            limit: MAX_DEPTH,
+        });
+    }
+    match val {
+        Array(arr) => {
+            for (i, v) in arr.iter().enumerate() {
+                check_depth(v, depth + 1, &pointer::push(path, &i.to_string()))?;
             }
-            let mut res = serde_json::Map::new();
-            for (k, v) in a {
-                let bv = b.get(k).unwrap();
-                if let (Array(_arr1), Array(_arr2)) = (v, bv) {
-                    if v.eq(bv) {
-                        res.insert(k.clone(), v.clone());
-                    } else {
-                        res.insert(k.clone(), Array(vec![v.clone(), bv.clone()]));
-                    }
-                } else if let (Array(arr1), _) = (v, bv) {
-                    let mut aaa = arr1.clone();
-                    if !aaa.contains(bv) {
-                        aaa.push(bv.clone());
-                    }
-                    res.insert(k.clone(), Array(aaa));
-                } else if v.eq(bv) {
-                    res.insert(k.clone(), v.clone());
-                } else {
-                    res.insert(k.clone(), Array(vec![v.clone(), bv.clone()]));
-                }
+        }
+        Object(obj) => {
+            for (k, v) in obj {
+                check_depth(v, depth + 1, &pointer::push(path, k))?;
             }
-            Ok(Object(res))
         }
-        _ => return Err(()),
+        _ => {}
     }
+    Ok(())
 }
-
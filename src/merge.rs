@@ -0,0 +1,359 @@
+use crate::{
+    dedup::{ArrayCompare, NumberCompare},
+    error::{Error, Result},
+    pointer::{self, PathPattern},
+    utils::{self, Compare, HashValue},
+};
+use serde_json::{
+    Map,
+    Value::{self, *},
+};
+use std::collections::HashMap;
+
+/// Where [`JsonUtils::merge_similar_with`](crate::JsonUtils::merge_similar_with) places a group of merged elements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Placement {
+    /// At the position of the first element of the group.
+    #[default]
+    First,
+    /// At the position of the last element of the group.
+    Last,
+}
+
+/// Options for [`JsonUtils::merge_similar_with`](crate::JsonUtils::merge_similar_with).
+#[derive(Clone, Debug, Default)]
+pub struct MergeSimilarOptions {
+    pub(crate) placement: Placement,
+    pub(crate) compare: Compare,
+}
+
+impl MergeSimilarOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets where merged groups are placed in the resulting array, `Placement::First` by default.
+    pub fn placement(mut self, placement: Placement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    /// Sets how numbers are compared, `NumberCompare::Exact` by default.
+    pub fn numbers(mut self, numbers: NumberCompare) -> Self {
+        self.compare.numbers = numbers;
+        self
+    }
+
+    /// Considers numbers equal when they differ by at most `epsilon`, regardless of how they are written.
+    pub fn epsilon(mut self, epsilon: f64) -> Self {
+        self.compare.epsilon = Some(epsilon);
+        self
+    }
+
+    /// Sets how arrays nested in the compared elements are compared, `ArrayCompare::Ordered` by default.
+    pub fn arrays(mut self, arrays: ArrayCompare) -> Self {
+        self.compare.arrays = arrays;
+        self
+    }
+
+    /// Sets how arrays at JSON pointers matching the pattern are compared. Pointers are relative
+    /// to the compared elements, so `/tags` refers to the `tags` member of every element.
+    /// Panics if the pattern is not a JSON pointer.
+    pub fn arrays_at(mut self, pattern: &str, arrays: ArrayCompare) -> Self {
+        self.compare.arrays_at.push((PathPattern::new(pattern), arrays));
+        self
+    }
+}
+
+/// How [`JsonUtils::deep_merge`](crate::JsonUtils::deep_merge) combines two arrays.
+#[derive(Clone, Debug, PartialEq, Eq, Default)]
+pub enum ArrayMerge {
+    /// The right array replaces the left one.
+    #[default]
+    Replace,
+    /// The elements of the right array are appended to the left one.
+    Append,
+    /// The elements of the right array that are not in the left one are appended to it.
+    Union,
+    /// Elements at the same index are merged, extra elements of the right array are appended.
+    ByIndex,
+    /// Elements having equal values at the given JSON pointer are merged, other
+    /// elements of the right array are appended. Merging fails with `Error::InvalidPointer`
+    /// if the key is not a JSON pointer, as in `"id"` instead of `"/id"`.
+    ByKey(std::string::String),
+}
+
+/// How [`JsonUtils::deep_merge`](crate::JsonUtils::deep_merge) resolves two different values
+/// that cannot be merged, such as two different strings or an object and a number.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Conflict {
+    /// The left value is kept.
+    LeftWins,
+    /// The right value is kept.
+    #[default]
+    RightWins,
+    /// Both values are collected into an array, or the right value is appended if the left one is an array.
+    Collect,
+    /// The merge fails with [`Error::IncompatibleMerge`].
+    Error,
+}
+
+/// Options for [`JsonUtils::deep_merge`](crate::JsonUtils::deep_merge).
+///
+/// Strategies set for path patterns take precedence over the default ones, in the
+/// order they were added. In patterns a `*` token matches any single key or index and
+/// a `**` token matches any number of them.
+#[derive(Clone, Debug, Default)]
+pub struct MergeOptions {
+    pub(crate) arrays: ArrayMerge,
+    pub(crate) conflicts: Conflict,
+    pub(crate) arrays_at: Vec<(PathPattern, ArrayMerge)>,
+    pub(crate) conflicts_at: Vec<(PathPattern, Conflict)>,
+}
+
+impl MergeOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets how arrays are combined, `ArrayMerge::Replace` by default.
+    pub fn arrays(mut self, arrays: ArrayMerge) -> Self {
+        self.arrays = arrays;
+        self
+    }
+
+    /// Sets how arrays at JSON pointers matching the pattern are combined. Panics if the pattern is not a JSON pointer.
+    pub fn arrays_at(mut self, pattern: &str, arrays: ArrayMerge) -> Self {
+        self.arrays_at.push((PathPattern::new(pattern), arrays));
+        self
+    }
+
+    /// Sets how conflicts are resolved, `Conflict::RightWins` by default.
+    pub fn conflicts(mut self, conflicts: Conflict) -> Self {
+        self.conflicts = conflicts;
+        self
+    }
+
+    /// Sets how conflicts at JSON pointers matching the pattern are resolved. Panics if the pattern is not a JSON pointer.
+    pub fn conflicts_at(mut self, pattern: &str, conflicts: Conflict) -> Self {
+        self.conflicts_at.push((PathPattern::new(pattern), conflicts));
+        self
+    }
+
+    fn arrays_for(&self, path: &str) -> &ArrayMerge {
+        self.arrays_at
+            .iter()
+            .find(|(p, _)| p.matches(path))
+            .map_or(&self.arrays, |(_, a)| a)
+    }
+
+    fn conflicts_for(&self, path: &str) -> Conflict {
+        self.conflicts_at
+            .iter()
+            .find(|(p, _)| p.matches(path))
+            .map_or(self.conflicts, |(_, c)| *c)
+    }
+}
+
+/// Recursively merge `right` into `left`
+pub(crate) fn deep_merge(left: &mut Value, right: Value, options: &MergeOptions, path: &str) -> Result<()> {
+    match (left, right) {
+        (Object(l), Object(r)) => {
+            for (k, rv) in r {
+                match l.get_mut(&k) {
+                    Some(lv) => deep_merge(lv, rv, options, &pointer::push(path, &k))?,
+                    None => {
+                        l.insert(k, rv);
+                    }
+                }
+            }
+        }
+        (Array(l), Array(r)) => match options.arrays_for(path) {
+            ArrayMerge::Replace => *l = r,
+            ArrayMerge::Append => l.extend(r),
+            ArrayMerge::Union => {
+                for rv in r {
+                    if !l.contains(&rv) {
+                        l.push(rv);
+                    }
+                }
+            }
+            ArrayMerge::ByIndex => {
+                for (i, rv) in r.into_iter().enumerate() {
+                    match l.get_mut(i) {
+                        Some(lv) => deep_merge(lv, rv, options, &pointer::push(path, &i.to_string()))?,
+                        None => l.push(rv),
+                    }
+                }
+            }
+            ArrayMerge::ByKey(key) => {
+                pointer::parse(key)?;
+                for rv in r {
+                    let found = rv
+                        .pointer(key)
+                        .and_then(|rk| l.iter().position(|lv| lv.pointer(key) == Some(rk)));
+                    match found {
+                        Some(i) => deep_merge(&mut l[i], rv, options, &pointer::push(path, &i.to_string()))?,
+                        None => l.push(rv),
+                    }
+                }
+            }
+        },
+        (l, r) => {
+            if *l == r {
+                return Ok(());
+            }
+            match options.conflicts_for(path) {
+                Conflict::LeftWins => {}
+                Conflict::RightWins => *l = r,
+                Conflict::Collect => match l {
+                    Array(arr) => {
+                        if !arr.contains(&r) {
+                            arr.push(r);
+                        }
+                    }
+                    _ => *l = Array(vec![std::mem::take(l), r]),
+                },
+                Conflict::Error => {
+                    return Err(Error::IncompatibleMerge {
+                        path: path.to_string(),
+                    })
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Apply a JSON Merge Patch (RFC 7396) to `target`
+pub(crate) fn merge_patch(target: &mut Value, patch: &Value) {
+    match patch {
+        Object(p) => {
+            if !target.is_object() {
+                *target = Object(Map::new());
+            }
+            if let Object(t) = target {
+                for (k, v) in p {
+                    if v.is_null() {
+                        utils::remove_key(t, k);
+                    } else {
+                        merge_patch(t.entry(k.clone()).or_insert(Null), v);
+                    }
+                }
+            }
+        }
+        _ => *target = patch.clone(),
+    }
+}
+
+/// Creates the JSON Merge Patch (RFC 7396) turning `from` into `to`.
+///
+/// Merge patches cannot set a member to `null` nor contain `null` inside an object that
+/// replaces a non-object value, so when `to` has `null` members the patch removes them instead.
+/// ## Example
+/// ```rust
+/// use serde_json::json;
+/// use serde_json_utils::{create_merge_patch, JsonUtils};
+///
+/// let mut x = json!({"title": "Goodbye!", "author": {"givenName": "John", "familyName": "Doe"}, "tags": ["example"]});
+/// let y = json!({"title": "Hello!", "author": {"givenName": "John"}, "tags": ["example", "sample"]});
+///
+/// let patch = create_merge_patch(&x, &y);
+/// assert_eq!(patch, json!({"title": "Hello!", "author": {"familyName": null}, "tags": ["example", "sample"]}));
+/// x.merge_patch(&patch);
+/// assert_eq!(x, y);
+/// ```
+pub fn create_merge_patch(from: &Value, to: &Value) -> Value {
+    match (from, to) {
+        (Object(f), Object(t)) => {
+            let mut patch = Map::new();
+            for k in f.keys() {
+                if !t.contains_key(k) {
+                    patch.insert(k.clone(), Null);
+                }
+            }
+            for (k, tv) in t {
+                match f.get(k) {
+                    Some(fv) if fv == tv => {}
+                    Some(fv) => {
+                        patch.insert(k.clone(), create_merge_patch(fv, tv));
+                    }
+                    None => {
+                        patch.insert(k.clone(), tv.clone());
+                    }
+                }
+            }
+            Object(patch)
+        }
+        _ => to.clone(),
+    }
+}
+
+/// Merge similar elements of arrays, keeping the order of the groups deterministic
+pub(crate) fn merge_similar(val: &mut Value, options: &MergeSimilarOptions) {
+    match val {
+        Array(arr) => {
+            // (first occurrence, last occurrence, merged value) of every group
+            let mut groups: Vec<(usize, usize, Value)> = vec![];
+            let mut index: HashMap<HashValue, usize> = HashMap::new();
+            for (i, v) in arr.drain(..).enumerate() {
+                if let Some(&g) = index.get(&HashValue(v.clone(), &options.compare)) {
+                    let group = &mut groups[g];
+                    if let Ok(m) = merge_similar_objects(&group.2, &v, &options.compare) {
+                        group.2 = m;
+                    }
+                    group.1 = i;
+                } else {
+                    index.insert(HashValue(v.clone(), &options.compare), groups.len());
+                    groups.push((i, i, v));
+                }
+            }
+            if options.placement == Placement::Last {
+                groups.sort_by_key(|g| g.1);
+            }
+            arr.extend(groups.into_iter().map(|g| g.2));
+        }
+        Object(obj) => {
+            for (_k, v) in obj {
+                merge_similar(v, options);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// merge similar objects
+pub(crate) fn merge_similar_objects(p: &Value, v: &Value, compare: &Compare) -> Result<Value> {
+    match (p, v) {
+        (Object(a), Object(b)) => {
+            if HashValue(p.clone(), compare) != HashValue(v.clone(), compare) {
+                return Err(Error::IncompatibleMerge { path: "".to_string() });
+            }
+            let mut res = serde_json::Map::new();
+            for (k, v) in a {
+                let bv = b.get(k).unwrap();
+                let path = compare.child("", k);
+                if let (Array(_arr1), Array(_arr2)) = (v, bv) {
+                    if compare.eq_at(v, bv, &path) {
+                        res.insert(k.clone(), v.clone());
+                    } else {
+                        res.insert(k.clone(), Array(vec![v.clone(), bv.clone()]));
+                    }
+                } else if let (Array(arr1), _) = (v, bv) {
+                    let mut aaa = arr1.clone();
+                    if !aaa.iter().enumerate().any(|(i, a)| compare.eq_at(a, bv, &compare.child(&path, i))) {
+                        aaa.push(bv.clone());
+                    }
+                    res.insert(k.clone(), Array(aaa));
+                } else if compare.eq_at(v, bv, &path) {
+                    res.insert(k.clone(), v.clone());
+                } else {
+                    res.insert(k.clone(), Array(vec![v.clone(), bv.clone()]));
+                }
+            }
+            Ok(Object(res))
+        }
+        _ => Err(Error::IncompatibleMerge { path: "".to_string() }),
+    }
+}
+
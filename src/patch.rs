@@ -0,0 +1,157 @@
+//! JSON Patch (RFC 6902) documents: applying them to values and generating them from two values.
+//! ## Example
+//! ```rust
+//! use serde_json::{from_value, json};
+//! use serde_json_utils::{patch::{diff, PatchOperation}, JsonUtils};
+//!
+//! let patch: Vec<PatchOperation> = from_value(json!([
+//!     {"op": "replace", "path": "/baz", "value": "boo"},
+//!     {"op": "add", "path": "/hello", "value": ["world"]},
+//!     {"op": "remove", "path": "/foo"}
+//! ])).unwrap();
+//!
+//! let mut x = json!({"baz": "qux", "foo": "bar"});
+//! let y = json!({"baz": "boo", "hello": ["world"]});
+//! x.apply_patch(&patch).unwrap();
+//! assert_eq!(x, y);
+//!
+//! let mut z = json!({"baz": "qux", "foo": "bar"});
+//! z.apply_patch(&diff(&z, &y)).unwrap();
+//! assert_eq!(z, y);
+//! ```
+use crate::{
+    dedup::NumberCompare,
+    error::{Error, Result},
+    pointer,
+    utils::Compare,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value::{self, *};
+
+/// An operation of a JSON Patch document, (de)serialized as in RFC 6902.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum PatchOperation {
+    /// Adds a value to an object or inserts it into an array, `-` appending to the array.
+    Add { path: std::string::String, value: Value },
+    /// Removes the value at `path`.
+    Remove { path: std::string::String },
+    /// Replaces the value at `path`, which must exist.
+    Replace { path: std::string::String, value: Value },
+    /// Removes the value at `from` and adds it at `path`.
+    Move {
+        from: std::string::String,
+        path: std::string::String,
+    },
+    /// Adds a copy of the value at `from` at `path`.
+    Copy {
+        from: std::string::String,
+        path: std::string::String,
+    },
+    /// Checks that the value at `path` equals `value`.
+    Test { path: std::string::String, value: Value },
+}
+
+/// Applies the operations in order, leaving `val` unchanged if any of them fails
+pub(crate) fn apply_patch(val: &mut Value, patch: &[PatchOperation]) -> Result<()> {
+    let mut patched = val.clone();
+    for op in patch {
+        apply(&mut patched, op)?;
+    }
+    *val = patched;
+    Ok(())
+}
+
+fn apply(val: &mut Value, op: &PatchOperation) -> Result<()> {
+    match op {
+        PatchOperation::Add { path, value } => pointer::add(val, path, value.clone()),
+        PatchOperation::Remove { path } => pointer::remove(val, path).map(|_| ()),
+        PatchOperation::Replace { path, value } => {
+            let tokens = pointer::parse(path)?;
+            *pointer::resolve_mut(val, &tokens, path)? = value.clone();
+            Ok(())
+        }
+        PatchOperation::Move { from, path } => {
+            // a value cannot be moved into one of its children
+            if path.starts_with(from.as_str()) && path[from.len()..].starts_with('/') {
+                return Err(Error::InvalidPointer { path: path.clone() });
+            }
+            let moved = pointer::remove(val, from)?;
+            pointer::add(val, path, moved)
+        }
+        PatchOperation::Copy { from, path } => {
+            let tokens = pointer::parse(from)?;
+            let copied = pointer::resolve(val, &tokens, from)?.clone();
+            pointer::add(val, path, copied)
+        }
+        PatchOperation::Test { path, value } => {
+            let tokens = pointer::parse(path)?;
+            // numbers are compared by their values, so `1` equals `1.0`
+            let compare = Compare {
+                numbers: NumberCompare::Semantic,
+                ..Compare::default()
+            };
+            if compare.eq(pointer::resolve(val, &tokens, path)?, value) {
+                Ok(())
+            } else {
+                Err(Error::TestFailed { path: path.clone() })
+            }
+        }
+    }
+}
+
+/// Generates a JSON Patch turning `from` into `to`.
+///
+/// Objects are compared key by key and arrays index by index, so inserting an element at
+/// the start of an array yields a replacement of every element rather than a single `add`.
+pub fn diff(from: &Value, to: &Value) -> Vec<PatchOperation> {
+    let mut patch = vec![];
+    diff_at(from, to, "", &mut patch);
+    patch
+}
+
+fn diff_at(from: &Value, to: &Value, path: &str, patch: &mut Vec<PatchOperation>) {
+    if from == to {
+        return;
+    }
+    match (from, to) {
+        (Object(f), Object(t)) => {
+            for (k, fv) in f {
+                let p = pointer::push(path, k);
+                match t.get(k) {
+                    Some(tv) => diff_at(fv, tv, &p, patch),
+                    None => patch.push(PatchOperation::Remove { path: p }),
+                }
+            }
+            for (k, tv) in t {
+                if !f.contains_key(k) {
+                    patch.push(PatchOperation::Add {
+                        path: pointer::push(path, k),
+                        value: tv.clone(),
+                    });
+                }
+            }
+        }
+        (Array(f), Array(t)) => {
+            for (i, (fv, tv)) in f.iter().zip(t).enumerate() {
+                diff_at(fv, tv, &pointer::push(path, &i.to_string()), patch);
+            }
+            // remove from the end so the indices stay valid
+            for i in (t.len()..f.len()).rev() {
+                patch.push(PatchOperation::Remove {
+                    path: pointer::push(path, &i.to_string()),
+                });
+            }
+            for tv in t.iter().skip(f.len()) {
+                patch.push(PatchOperation::Add {
+                    path: pointer::push(path, "-"),
+                    value: tv.clone(),
+                });
+            }
+        }
+        _ => patch.push(PatchOperation::Replace {
+            path: path.to_string(),
+            value: to.clone(),
+        }),
+    }
+}
@@ -0,0 +1,297 @@
+use crate::{
+    error::{Error, Result},
+    utils,
+};
+use serde_json::Value;
+
+/// Appends `token` to the JSON pointer `path`, escaping `~` and `/` as per RFC 6901.
+pub(crate) fn push(path: &str, token: &str) -> String {
+    let mut p = String::with_capacity(path.len() + token.len() + 1);
+    p.push_str(path);
+    p.push('/');
+    for c in token.chars() {
+        match c {
+            '~' => p.push_str("~0"),
+            '/' => p.push_str("~1"),
+            _ => p.push(c),
+        }
+    }
+    p
+}
+
+/// Form of a JSON pointer in messages, where the empty pointer of the root value reads `(root)`.
+pub(crate) fn display(path: &str) -> &str {
+    if path.is_empty() {
+        "(root)"
+    } else {
+        path
+    }
+}
+
+/// Name of the json type of a value, used in error messages.
+pub(crate) fn kind(val: &Value) -> &'static str {
+    match val {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// A JSON pointer in which the `*` token matches any single token and
+/// the `**` token matches any number of tokens, including none.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct PathPattern(Vec<String>);
+
+impl PathPattern {
+    /// Panics if the pattern is not a JSON pointer, since the caller would otherwise
+    /// silently get a pattern matching nothing.
+    pub fn new(pattern: &str) -> Self {
+        match parse(pattern) {
+            Ok(tokens) => PathPattern(tokens),
+            Err(_) => panic!("invalid JSON pointer pattern {:?}, patterns start with `/`", pattern),
+        }
+    }
+
+    /// Whether the JSON pointer `path` matches the pattern
+    pub fn matches(&self, path: &str) -> bool {
+        parse(path).map_or(false, |path| matches_tokens(&self.0, &path))
+    }
+}
+
+fn matches_tokens(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((p, rest)) if p == "**" => {
+            (0..=path.len()).any(|i| matches_tokens(rest, &path[i..]))
+        }
+        Some((p, rest)) => match path.split_first() {
+            Some((t, path_rest)) => (p == "*" || p == t) && matches_tokens(rest, path_rest),
+            None => false,
+        },
+    }
+}
+
+/// Parses a JSON pointer into its unescaped reference tokens, failing on malformed pointers.
+pub(crate) fn parse(path: &str) -> Result<Vec<String>> {
+    if path.is_empty() {
+        return Ok(vec![]);
+    }
+    if !path.starts_with('/') {
+        return Err(Error::InvalidPointer { path: path.to_string() });
+    }
+    path.split('/')
+        .skip(1)
+        .map(|t| {
+            let mut token = String::with_capacity(t.len());
+            let mut chars = t.chars();
+            while let Some(c) = chars.next() {
+                if c != '~' {
+                    token.push(c);
+                    continue;
+                }
+                match chars.next() {
+                    Some('0') => token.push('~'),
+                    Some('1') => token.push('/'),
+                    _ => return Err(Error::InvalidPointer { path: path.to_string() }),
+                }
+            }
+            Ok(token)
+        })
+        .collect()
+}
+
+/// Parses an array index, which has no leading zeros
+pub(crate) fn index(token: &str, path: &str) -> Result<usize> {
+    if token.is_empty() || (token.len() > 1 && token.starts_with('0')) || !token.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(Error::InvalidPointer { path: path.to_string() });
+    }
+    token.parse().map_err(|_| Error::InvalidPointer { path: path.to_string() })
+}
+
+/// Returns the value at the given tokens of the pointer `path`
+pub(crate) fn resolve<'a>(mut val: &'a Value, tokens: &[String], path: &str) -> Result<&'a Value> {
+    for token in tokens {
+        val = match val {
+            Value::Object(obj) => obj.get(token),
+            Value::Array(arr) => arr.get(index(token, path)?),
+            _ => return Err(scalar_error(val, path)),
+        }
+        .ok_or_else(|| Error::PathNotFound { path: path.to_string() })?;
+    }
+    Ok(val)
+}
+
+/// Returns the value at the given tokens of the pointer `path`
+pub(crate) fn resolve_mut<'a>(mut val: &'a mut Value, tokens: &[String], path: &str) -> Result<&'a mut Value> {
+    for token in tokens {
+        val = match val {
+            Value::Object(obj) => obj.get_mut(token),
+            Value::Array(arr) => arr.get_mut(index(token, path)?),
+            _ => return Err(scalar_error(val, path)),
+        }
+        .ok_or_else(|| Error::PathNotFound { path: path.to_string() })?;
+    }
+    Ok(val)
+}
+
+/// Adds `new` at `path`: replaces the field of an object, or inserts the element of an
+/// array, where `-` appends to the array.
+pub(crate) fn add(root: &mut Value, path: &str, new: Value) -> Result<()> {
+    let tokens = parse(path)?;
+    let (last, parent) = match tokens.split_last() {
+        Some(split) => split,
+        None => {
+            *root = new;
+            return Ok(());
+        }
+    };
+    match resolve_mut(root, parent, path)? {
+        Value::Object(obj) => {
+            obj.insert(last.clone(), new);
+        }
+        Value::Array(arr) => {
+            let i = if last == "-" { arr.len() } else { index(last, path)? };
+            if i > arr.len() {
+                return Err(Error::PathNotFound { path: path.to_string() });
+            }
+            arr.insert(i, new);
+        }
+        val => return Err(scalar_error(val, path)),
+    }
+    Ok(())
+}
+
+/// Removes the value at `path` and returns it. The root value is replaced by `null`.
+pub(crate) fn remove(root: &mut Value, path: &str) -> Result<Value> {
+    let tokens = parse(path)?;
+    let (last, parent) = match tokens.split_last() {
+        Some(split) => split,
+        None => return Ok(std::mem::take(root)),
+    };
+    match resolve_mut(root, parent, path)? {
+        Value::Object(obj) => utils::remove_key(obj, last),
+        Value::Array(arr) => {
+            let i = index(last, path)?;
+            if i < arr.len() {
+                Some(arr.remove(i))
+            } else {
+                None
+            }
+        }
+        val => return Err(scalar_error(val, path)),
+    }
+    .ok_or_else(|| Error::PathNotFound { path: path.to_string() })
+}
+
+fn scalar_error(val: &Value, path: &str) -> Error {
+    Error::ShapeMismatch {
+        path: path.to_string(),
+        expected: "object or array",
+        found: kind(val),
+    }
+}
+
+/// Container indexed by `token` when it has to be created: an array for `-` and numbers,
+/// unless the token is known to be an object key
+fn container_for(token: &str, key: bool) -> Value {
+    if !key && (token == "-" || index(token, "").is_ok()) {
+        Value::Array(vec![])
+    } else {
+        Value::Object(Default::default())
+    }
+}
+
+fn is_key(keys: &[bool], i: usize) -> bool {
+    keys.get(i).copied().unwrap_or(false)
+}
+
+/// Fails like `vivify` and `set` would, without changing anything
+fn check_set(root: &Value, tokens: &[String], keys: &[bool], path: &str) -> Result<()> {
+    let mut val = Some(root);
+    for (i, token) in tokens.iter().enumerate() {
+        val = match val.filter(|v| !v.is_null()) {
+            Some(Value::Object(obj)) => obj.get(token),
+            Some(Value::Array(arr)) => {
+                let i = if token == "-" { arr.len() } else { index(token, path)? };
+                if i > arr.len() {
+                    return Err(Error::PathNotFound { path: path.to_string() });
+                }
+                arr.get(i)
+            }
+            Some(val) => return Err(scalar_error(val, path)),
+            // created containers are empty, so only appending to new arrays works
+            None if is_key(keys, i) || token == "-" || index(token, path).map_or(true, |i| i == 0) => None,
+            None => return Err(Error::PathNotFound { path: path.to_string() }),
+        };
+    }
+    Ok(())
+}
+
+/// Returns the parent of the value at `tokens`, creating the missing objects and arrays
+/// on the way. `null` values on the way are replaced as well.
+fn vivify<'a>(mut val: &'a mut Value, tokens: &[String], keys: &[bool], path: &str) -> Result<&'a mut Value> {
+    for (i, token) in tokens.iter().enumerate() {
+        if val.is_null() {
+            *val = container_for(token, is_key(keys, i));
+        }
+        if i + 1 == tokens.len() {
+            break;
+        }
+        let next = &tokens[i + 1];
+        val = match val {
+            Value::Object(obj) => obj.entry(token.clone()).or_insert_with(|| container_for(next, is_key(keys, i + 1))),
+            Value::Array(arr) => {
+                let j = if token == "-" { arr.len() } else { index(token, path)? };
+                if j == arr.len() {
+                    arr.push(container_for(next, is_key(keys, i + 1)));
+                }
+                arr.get_mut(j)
+                    .ok_or_else(|| Error::PathNotFound { path: path.to_string() })?
+            }
+            _ => return Err(scalar_error(val, path)),
+        };
+    }
+    Ok(val)
+}
+
+/// Sets the value at `path`, creating the missing parents, and returns the replaced value.
+/// In arrays `-` and the length of the array append the value, and other indices replace
+/// the element unless `insert` is set. The value is left unchanged on failure.
+pub(crate) fn set(root: &mut Value, path: &str, new: Value, insert: bool) -> Result<Option<Value>> {
+    set_tokens(root, &parse(path)?, &[], path, new, insert)
+}
+
+/// Same as `set` with the parsed `tokens` of `path`, where the tokens flagged in `keys`
+/// create objects even if they look like array indices.
+pub(crate) fn set_tokens(
+    root: &mut Value,
+    tokens: &[String],
+    keys: &[bool],
+    path: &str,
+    new: Value,
+    insert: bool,
+) -> Result<Option<Value>> {
+    let last = match tokens.last() {
+        Some(last) => last,
+        None => return Ok(Some(std::mem::replace(root, new))),
+    };
+    check_set(root, tokens, keys, path)?;
+    match vivify(root, tokens, keys, path)? {
+        Value::Object(obj) => Ok(obj.insert(last.clone(), new)),
+        Value::Array(arr) => {
+            let i = if last == "-" { arr.len() } else { index(last, path)? };
+            if i > arr.len() {
+                Err(Error::PathNotFound { path: path.to_string() })
+            } else if i == arr.len() || insert {
+                arr.insert(i, new);
+                Ok(None)
+            } else {
+                Ok(Some(std::mem::replace(&mut arr[i], new)))
+            }
+        }
+        val => Err(scalar_error(val, path)),
+    }
+}
@@ -0,0 +1,218 @@
+use crate::pointer::{self, PathPattern};
+use serde_json::Value::{self, *};
+
+/// Options for [`JsonUtils::prune_with`](crate::JsonUtils::prune_with), selecting which values are removed.
+///
+/// By default only `null` values are removed, like `skip_null` does.
+#[derive(Clone, Debug)]
+pub struct PruneOptions {
+    pub(crate) nulls: bool,
+    pub(crate) empty_arrays: bool,
+    pub(crate) empty_objects: bool,
+    pub(crate) empty_strings: bool,
+    pub(crate) blank_strings: bool,
+    pub(crate) falses: bool,
+    pub(crate) zeros: bool,
+    pub(crate) cascade: bool,
+    pub(crate) include: Vec<PathPattern>,
+    pub(crate) exclude: Vec<PathPattern>,
+}
+
+impl Default for PruneOptions {
+    fn default() -> Self {
+        PruneOptions {
+            nulls: true,
+            empty_arrays: false,
+            empty_objects: false,
+            empty_strings: false,
+            blank_strings: false,
+            falses: false,
+            zeros: false,
+            cascade: false,
+            include: vec![],
+            exclude: vec![],
+        }
+    }
+}
+
+impl PruneOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes `null` values, `true` by default.
+    pub fn nulls(mut self, remove: bool) -> Self {
+        self.nulls = remove;
+        self
+    }
+
+    /// Removes `[]` values.
+    pub fn empty_arrays(mut self, remove: bool) -> Self {
+        self.empty_arrays = remove;
+        self
+    }
+
+    /// Removes `{}` values.
+    pub fn empty_objects(mut self, remove: bool) -> Self {
+        self.empty_objects = remove;
+        self
+    }
+
+    /// Removes `""` values.
+    pub fn empty_strings(mut self, remove: bool) -> Self {
+        self.empty_strings = remove;
+        self
+    }
+
+    /// Removes strings that are empty or contain only whitespace.
+    pub fn blank_strings(mut self, remove: bool) -> Self {
+        self.blank_strings = remove;
+        self
+    }
+
+    /// Removes `false` values.
+    pub fn falses(mut self, remove: bool) -> Self {
+        self.falses = remove;
+        self
+    }
+
+    /// Removes numbers equal to zero.
+    pub fn zeros(mut self, remove: bool) -> Self {
+        self.zeros = remove;
+        self
+    }
+
+    /// Checks values after their children have been pruned, so that arrays and objects
+    /// that become empty are removed as well, e.g. `{"a": {"b": null}}` becomes `{}`.
+    pub fn cascade(mut self, cascade: bool) -> Self {
+        self.cascade = cascade;
+        self
+    }
+
+    /// Only removes values at JSON pointers matching one of the included patterns, when there are any.
+    /// A `*` token matches any single key or index and a `**` token matches any number of them.
+    /// Panics if the pattern is not a JSON pointer.
+    pub fn include(mut self, pattern: &str) -> Self {
+        self.include.push(PathPattern::new(pattern));
+        self
+    }
+
+    /// Never removes values at JSON pointers matching the pattern, so `/patch/**` keeps
+    /// everything under `/patch`. A `*` token matches any single key or index and a `**`
+    /// token matches any number of them. Panics if the pattern is not a JSON pointer.
+    pub fn exclude(mut self, pattern: &str) -> Self {
+        self.exclude.push(PathPattern::new(pattern));
+        self
+    }
+
+    /// Options of `skip_null_and_empty`
+    pub(crate) fn null_and_empty() -> Self {
+        Self::default().empty_arrays(true).empty_objects(true)
+    }
+
+    /// Whether the value at `path` may be removed
+    fn in_scope(&self, path: &str) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|p| p.matches(path)))
+            && !self.exclude.iter().any(|p| p.matches(path))
+    }
+
+    /// Whether `val` is to be removed
+    pub(crate) fn is_empty(&self, val: &Value) -> bool {
+        match val {
+            Null => self.nulls,
+            Bool(b) => self.falses && !b,
+            Number(n) => self.zeros && n.as_f64() == Some(0.0),
+            String(s) => {
+                (self.empty_strings && s.is_empty()) || (self.blank_strings && s.trim().is_empty())
+            }
+            Array(arr) => self.empty_arrays && arr.is_empty(),
+            Object(obj) => self.empty_objects && obj.is_empty(),
+        }
+    }
+}
+
+/// Field or element removed by [`JsonUtils::prune_with_report`](crate::JsonUtils::prune_with_report).
+#[derive(Clone, Debug, PartialEq)]
+pub struct Removed {
+    /// JSON pointer of the removed value, using the indices of arrays before their elements were removed.
+    pub path: std::string::String,
+    /// The removed value, when it was requested.
+    pub value: Option<Value>,
+}
+
+/// Removes the fields & elements for which a predicate returns true
+pub(crate) struct Pruner<F> {
+    pub predicate: F,
+    /// Call the predicate after pruning the children instead of before
+    pub cascade: bool,
+    /// Whether the predicate or the report need the paths
+    pub track: bool,
+    /// Removed values are recorded in the report when set, with their value when the flag is set
+    pub report: Option<(Vec<Removed>, bool)>,
+}
+
+impl<F: FnMut(&str, &Value) -> bool> Pruner<F> {
+    pub fn prune(&mut self, val: &mut Value, path: &str) {
+        match val {
+            Array(arr) => {
+                let items = std::mem::take(arr);
+                for (i, mut v) in items.into_iter().enumerate() {
+                    let p = self.child_path(path, &i.to_string());
+                    if self.prune_child(&mut v, &p) {
+                        self.record(p, &mut v);
+                    } else {
+                        arr.push(v);
+                    }
+                }
+            }
+            Object(obj) => obj.retain(|k, v| {
+                let p = self.child_path(path, k);
+                let remove = self.prune_child(v, &p);
+                if remove {
+                    self.record(p, v);
+                }
+                !remove
+            }),
+            _ => {}
+        }
+    }
+
+    /// Prune a field or element, returning whether it is to be removed itself
+    fn prune_child(&mut self, val: &mut Value, path: &str) -> bool {
+        if !self.cascade && (self.predicate)(path, val) {
+            return true;
+        }
+        self.prune(val, path);
+        self.cascade && (self.predicate)(path, val)
+    }
+
+    fn record(&mut self, path: std::string::String, val: &mut Value) {
+        if let Some((removed, with_values)) = &mut self.report {
+            removed.push(Removed {
+                path,
+                value: if *with_values { Some(std::mem::take(val)) } else { None },
+            });
+        }
+    }
+
+    fn child_path(&self, path: &str, token: &str) -> std::string::String {
+        if self.track {
+            pointer::push(path, token)
+        } else {
+            std::string::String::new()
+        }
+    }
+}
+
+/// Remove the fields & elements of `val` selected by the options, reporting
+/// them when `report` is set, with their values when it is true
+pub(crate) fn prune(val: &mut Value, options: &PruneOptions, report: Option<bool>) -> Vec<Removed> {
+    let mut pruner = Pruner {
+        predicate: |p: &str, v: &Value| options.is_empty(v) && options.in_scope(p),
+        cascade: options.cascade,
+        track: report.is_some() || !options.include.is_empty() || !options.exclude.is_empty(),
+        report: report.map(|with_values| (vec![], with_values)),
+    };
+    pruner.prune(val, "");
+    pruner.report.map(|r| r.0).unwrap_or_default()
+}
@@ -0,0 +1,973 @@
+use crate::{
+    dedup::NumberCompare,
+    error::{Error, Result},
+    utils::Compare,
+    MAX_DEPTH,
+};
+#[cfg(feature = "regex")]
+use regex::Regex;
+use serde_json::Value;
+use std::{borrow::Cow, cmp::Ordering, fmt::Write};
+
+/// Largest integer allowed in index and slice selectors, `2^53 - 1`.
+const MAX_INT: i64 = 9_007_199_254_740_991;
+
+/// Node selected by [`JsonUtils::query`](crate::JsonUtils::query).
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryMatch<'a> {
+    /// Normalized path of the node, such as `$['items'][0]`.
+    pub path: String,
+    /// The value of the node.
+    pub value: &'a Value,
+}
+
+/// Segment of the location of a node
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Location {
+    Key(String),
+    Index(usize),
+}
+
+type Node<'a> = (Vec<Location>, &'a Value);
+
+#[derive(Debug)]
+struct Query {
+    segments: Vec<Segment>,
+}
+
+#[derive(Debug)]
+enum Segment {
+    Child(Vec<Selector>),
+    Descendant(Vec<Selector>),
+}
+
+#[derive(Debug)]
+enum Selector {
+    Name(String),
+    Wildcard,
+    Index(i64),
+    Slice(Option<i64>, Option<i64>, Option<i64>),
+    Filter(Logical),
+}
+
+#[derive(Debug)]
+enum Logical {
+    Or(Vec<Logical>),
+    And(Vec<Logical>),
+    Not(Box<Logical>),
+    Comparison(Comparable, Op, Comparable),
+    Exists(FilterQuery),
+    Function(FunctionExpr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug)]
+struct FilterQuery {
+    /// Whether the query starts at the current node `@` rather than the root `$`
+    relative: bool,
+    query: Query,
+}
+
+#[derive(Debug)]
+enum Comparable {
+    Literal(Value),
+    Query(FilterQuery),
+    Function(FunctionExpr),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Function {
+    Length,
+    Count,
+    #[cfg(feature = "regex")]
+    Match,
+    #[cfg(feature = "regex")]
+    Search,
+    Value,
+}
+
+/// Types of the parameters and results of functions, as defined by RFC 9535
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "regex"), allow(dead_code))]
+enum Type {
+    Value,
+    Logical,
+    Nodes,
+}
+
+impl Function {
+    fn signature(self) -> (&'static [Type], Type) {
+        match self {
+            Function::Length => (&[Type::Value], Type::Value),
+            Function::Count => (&[Type::Nodes], Type::Value),
+            #[cfg(feature = "regex")]
+            Function::Match | Function::Search => (&[Type::Value, Type::Value], Type::Logical),
+            Function::Value => (&[Type::Nodes], Type::Value),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct FunctionExpr {
+    function: Function,
+    args: Vec<Argument>,
+    /// Regex of the literal pattern of `match` and `search`, compiled once when parsing,
+    /// `Some(None)` if the pattern is not a valid I-Regexp
+    #[cfg(feature = "regex")]
+    regex: Option<Option<Regex>>,
+}
+
+#[derive(Debug)]
+enum Argument {
+    Literal(Value),
+    Query(FilterQuery),
+    Function(FunctionExpr),
+}
+
+/// Value of a function argument or result
+#[cfg_attr(not(feature = "regex"), allow(dead_code))]
+enum Evaluated<'a> {
+    /// A value, `None` being the absence of value
+    Value(Option<Cow<'a, Value>>),
+    Logical(bool),
+    Nodes(Vec<&'a Value>),
+}
+
+/// Parser of RFC 9535 JSONPath queries
+struct Parser<'s> {
+    src: &'s str,
+    pos: usize,
+    /// Number of enclosing logical expressions and function calls
+    depth: usize,
+}
+
+impl<'s> Parser<'s> {
+    fn error<T>(&self, message: &str) -> Result<T> {
+        Err(Error::InvalidQuery {
+            query: self.src.to_string(),
+            message: format!("{} at position {}", message, self.pos),
+        })
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.src[self.pos..].chars().next()
+    }
+
+    fn rest(&self) -> &'s str {
+        &self.src[self.pos..]
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        if self.rest().starts_with(s) {
+            self.pos += s.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, s: &str) -> Result<()> {
+        if self.eat(s) {
+            Ok(())
+        } else {
+            self.error(&format!("expected `{}`", s))
+        }
+    }
+
+    /// Parses a nested expression, failing beyond [`MAX_DEPTH`] levels of nesting
+    fn nested<T>(&mut self, parse: fn(&mut Self) -> Result<T>) -> Result<T> {
+        if self.depth == MAX_DEPTH {
+            return self.error(&format!("nesting deeper than {}", MAX_DEPTH));
+        }
+        self.depth += 1;
+        let res = parse(self);
+        self.depth -= 1;
+        res
+    }
+
+    fn blank(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    fn query(&mut self) -> Result<Query> {
+        self.expect("$")?;
+        let query = self.segments()?;
+        if self.pos < self.src.len() {
+            return self.error("unexpected character");
+        }
+        Ok(query)
+    }
+
+    fn segments(&mut self) -> Result<Query> {
+        let mut segments = vec![];
+        loop {
+            let start = self.pos;
+            self.blank();
+            if self.eat("..") {
+                let selectors = match self.peek() {
+                    Some('[') => self.bracketed()?,
+                    Some('*') => {
+                        self.pos += 1;
+                        vec![Selector::Wildcard]
+                    }
+                    _ => vec![Selector::Name(self.member_name()?)],
+                };
+                segments.push(Segment::Descendant(selectors));
+            } else if self.eat(".") {
+                if self.eat("*") {
+                    segments.push(Segment::Child(vec![Selector::Wildcard]));
+                } else {
+                    segments.push(Segment::Child(vec![Selector::Name(self.member_name()?)]));
+                }
+            } else if self.peek() == Some('[') {
+                segments.push(Segment::Child(self.bracketed()?));
+            } else {
+                // the blank space belongs to the enclosing expression
+                self.pos = start;
+                return Ok(Query { segments });
+            }
+        }
+    }
+
+    fn member_name(&mut self) -> Result<String> {
+        let name_first = |c: char| c.is_ascii_alphabetic() || c == '_' || !c.is_ascii();
+        match self.peek() {
+            Some(c) if name_first(c) => {}
+            _ => return self.error("expected a member name"),
+        }
+        let len = self
+            .rest()
+            .find(|c: char| !(name_first(c) || c.is_ascii_digit()))
+            .unwrap_or_else(|| self.rest().len());
+        let name = self.rest()[..len].to_string();
+        self.pos += len;
+        Ok(name)
+    }
+
+    fn bracketed(&mut self) -> Result<Vec<Selector>> {
+        self.expect("[")?;
+        let mut selectors = vec![];
+        loop {
+            self.blank();
+            selectors.push(self.selector()?);
+            self.blank();
+            if self.eat("]") {
+                return Ok(selectors);
+            }
+            self.expect(",")?;
+        }
+    }
+
+    fn selector(&mut self) -> Result<Selector> {
+        match self.peek() {
+            Some('\'' | '"') => Ok(Selector::Name(self.string()?)),
+            Some('*') => {
+                self.pos += 1;
+                Ok(Selector::Wildcard)
+            }
+            Some('?') => {
+                self.pos += 1;
+                self.blank();
+                Ok(Selector::Filter(self.nested(Self::logical)?))
+            }
+            _ => self.index_or_slice(),
+        }
+    }
+
+    fn index_or_slice(&mut self) -> Result<Selector> {
+        let start = self.int_opt()?;
+        self.blank();
+        if !self.eat(":") {
+            return match start {
+                Some(i) => Ok(Selector::Index(i)),
+                None => self.error("expected a selector"),
+            };
+        }
+        self.blank();
+        let end = self.int_opt()?;
+        self.blank();
+        let step = if self.eat(":") {
+            self.blank();
+            self.int_opt()?
+        } else {
+            None
+        };
+        Ok(Selector::Slice(start, end, step))
+    }
+
+    /// Integer of an index or slice selector, if there is one
+    fn int_opt(&mut self) -> Result<Option<i64>> {
+        let rest = self.rest();
+        let digits = rest.strip_prefix('-').unwrap_or(rest);
+        let len = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+        if len == 0 {
+            return if digits.len() < rest.len() {
+                self.error("expected an integer")
+            } else {
+                Ok(None)
+            };
+        }
+        let text = &rest[..rest.len() - digits.len() + len];
+        if (len > 1 && digits.starts_with('0')) || text == "-0" {
+            return self.error("invalid integer");
+        }
+        match text.parse::<i64>() {
+            Ok(i) if (-MAX_INT..=MAX_INT).contains(&i) => {
+                self.pos += text.len();
+                Ok(Some(i))
+            }
+            _ => self.error("integer out of range"),
+        }
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let quote = match self.peek() {
+            Some(q @ ('\'' | '"')) => q,
+            _ => return self.error("expected a string"),
+        };
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return self.error("unterminated string"),
+            };
+            self.pos += c.len_utf8();
+            match c {
+                _ if c == quote => return Ok(s),
+                '\\' => {
+                    let e = self.peek();
+                    self.pos += 1;
+                    match e {
+                        Some('b') => s.push('\u{8}'),
+                        Some('f') => s.push('\u{c}'),
+                        Some('n') => s.push('\n'),
+                        Some('r') => s.push('\r'),
+                        Some('t') => s.push('\t'),
+                        Some('/') => s.push('/'),
+                        Some('\\') => s.push('\\'),
+                        Some(q) if q == quote => s.push(q),
+                        Some('u') => s.push(self.unicode_escape()?),
+                        _ => return self.error("invalid escape"),
+                    }
+                }
+                '\u{0}'..='\u{1f}' => return self.error("control character in string"),
+                _ => s.push(c),
+            }
+        }
+    }
+
+    /// Character of a `\uXXXX` escape, which may be followed by a low surrogate
+    fn unicode_escape(&mut self) -> Result<char> {
+        let hex = |p: &mut Self| -> Result<u32> {
+            match p.src.get(p.pos..p.pos + 4).and_then(|h| u32::from_str_radix(h, 16).ok()) {
+                Some(h) if p.src[p.pos..p.pos + 4].bytes().all(|b| b.is_ascii_hexdigit()) => {
+                    p.pos += 4;
+                    Ok(h)
+                }
+                _ => p.error("invalid unicode escape"),
+            }
+        };
+        let high = hex(self)?;
+        let code = match high {
+            0xD800..=0xDBFF => {
+                self.expect("\\u")?;
+                let low = hex(self)?;
+                if !(0xDC00..=0xDFFF).contains(&low) {
+                    return self.error("invalid surrogate pair");
+                }
+                0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+            }
+            0xDC00..=0xDFFF => return self.error("invalid surrogate pair"),
+            _ => high,
+        };
+        match char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => self.error("invalid unicode escape"),
+        }
+    }
+
+    fn logical(&mut self) -> Result<Logical> {
+        let mut or = vec![self.logical_and()?];
+        loop {
+            let start = self.pos;
+            self.blank();
+            if !self.eat("||") {
+                self.pos = start;
+                break;
+            }
+            self.blank();
+            or.push(self.logical_and()?);
+        }
+        Ok(if or.len() == 1 { or.remove(0) } else { Logical::Or(or) })
+    }
+
+    fn logical_and(&mut self) -> Result<Logical> {
+        let mut and = vec![self.basic()?];
+        loop {
+            let start = self.pos;
+            self.blank();
+            if !self.eat("&&") {
+                self.pos = start;
+                break;
+            }
+            self.blank();
+            and.push(self.basic()?);
+        }
+        Ok(if and.len() == 1 { and.remove(0) } else { Logical::And(and) })
+    }
+
+    fn basic(&mut self) -> Result<Logical> {
+        if self.eat("!") {
+            self.blank();
+            return self.negation();
+        }
+        if self.peek() == Some('(') {
+            return self.paren();
+        }
+        let left = self.comparable()?;
+        let start = self.pos;
+        self.blank();
+        match self.op() {
+            Some(op) => self.comparison(left, op),
+            None => {
+                self.pos = start;
+                match left {
+                    Comparable::Query(q) => Ok(Logical::Exists(q)),
+                    Comparable::Function(f) => self.test_function(f),
+                    Comparable::Literal(_) => self.error("expected a comparison"),
+                }
+            }
+        }
+    }
+
+    /// Negated expression, after the `!`
+    fn negation(&mut self) -> Result<Logical> {
+        if self.peek() == Some('(') {
+            return Ok(Logical::Not(Box::new(self.paren()?)));
+        }
+        match self.comparable()? {
+            Comparable::Query(q) => Ok(Logical::Not(Box::new(Logical::Exists(q)))),
+            Comparable::Function(f) => Ok(Logical::Not(Box::new(self.test_function(f)?))),
+            Comparable::Literal(_) => self.error("expected a query or a function"),
+        }
+    }
+
+    /// Comparison of `left`, after the operator
+    fn comparison(&mut self, left: Comparable, op: Op) -> Result<Logical> {
+        self.blank();
+        let right = self.comparable()?;
+        for c in [&left, &right] {
+            match c {
+                Comparable::Query(q) if !q.query.is_singular() => {
+                    return self.error("only singular queries can be compared")
+                }
+                Comparable::Function(f) if f.function.signature().1 != Type::Value => {
+                    return self.error("function without value result compared")
+                }
+                _ => {}
+            }
+        }
+        Ok(Logical::Comparison(left, op, right))
+    }
+
+    fn test_function(&self, f: FunctionExpr) -> Result<Logical> {
+        if f.function.signature().1 == Type::Value {
+            return self.error("function with value result used as a test");
+        }
+        Ok(Logical::Function(f))
+    }
+
+    fn paren(&mut self) -> Result<Logical> {
+        self.expect("(")?;
+        self.blank();
+        let expr = self.nested(Self::logical)?;
+        self.blank();
+        self.expect(")")?;
+        Ok(expr)
+    }
+
+    fn op(&mut self) -> Option<Op> {
+        let ops = [
+            ("==", Op::Eq),
+            ("!=", Op::Ne),
+            ("<=", Op::Le),
+            (">=", Op::Ge),
+            ("<", Op::Lt),
+            (">", Op::Gt),
+        ];
+        ops.iter().find(|(s, _)| self.eat(s)).map(|(_, op)| *op)
+    }
+
+    fn comparable(&mut self) -> Result<Comparable> {
+        match self.peek() {
+            Some('@') => {
+                self.pos += 1;
+                let query = self.segments()?;
+                Ok(Comparable::Query(FilterQuery { relative: true, query }))
+            }
+            Some('$') => {
+                self.pos += 1;
+                let query = self.segments()?;
+                Ok(Comparable::Query(FilterQuery { relative: false, query }))
+            }
+            Some('\'' | '"') => Ok(Comparable::Literal(Value::String(self.string()?))),
+            Some('-' | '0'..='9') => Ok(Comparable::Literal(self.number()?)),
+            Some('a'..='z') => match self.keyword() {
+                Some(value) => Ok(Comparable::Literal(value)),
+                None => Ok(Comparable::Function(self.nested(Self::function)?)),
+            },
+            _ => self.error("expected a literal, a query or a function"),
+        }
+    }
+
+    /// Literal `true`, `false` or `null`, if there is one
+    fn keyword(&mut self) -> Option<Value> {
+        for (word, value) in [("true", Value::Bool(true)), ("false", Value::Bool(false)), ("null", Value::Null)] {
+            if self.rest().starts_with(word)
+                && !self.rest()[word.len()..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '(')
+            {
+                self.pos += word.len();
+                return Some(value);
+            }
+        }
+        None
+    }
+
+    fn number(&mut self) -> Result<Value> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')))
+            .unwrap_or(rest.len());
+        let text = &rest[..len];
+        match serde_json::from_str::<serde_json::Number>(text) {
+            Ok(n) if !text.starts_with("-.") => {
+                self.pos += len;
+                Ok(Value::Number(n))
+            }
+            _ => self.error("invalid number"),
+        }
+    }
+
+    fn function(&mut self) -> Result<FunctionExpr> {
+        let len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_'))
+            .unwrap_or(self.rest().len());
+        let function = match &self.rest()[..len] {
+            "length" => Function::Length,
+            "count" => Function::Count,
+            #[cfg(feature = "regex")]
+            "match" => Function::Match,
+            #[cfg(feature = "regex")]
+            "search" => Function::Search,
+            #[cfg(not(feature = "regex"))]
+            "match" | "search" => return self.error("match() and search() require the `regex` feature"),
+            "value" => Function::Value,
+            _ => return self.error("unknown function"),
+        };
+        self.pos += len;
+        self.expect("(")?;
+        let (params, _) = function.signature();
+        let mut args = vec![];
+        for (i, param) in params.iter().enumerate() {
+            self.blank();
+            if i > 0 {
+                self.expect(",")?;
+                self.blank();
+            }
+            let arg = match self.comparable()? {
+                Comparable::Literal(v) if *param == Type::Value => Argument::Literal(v),
+                Comparable::Query(q) if *param == Type::Nodes || q.query.is_singular() => Argument::Query(q),
+                Comparable::Function(f) if f.function.signature().1 == *param => Argument::Function(f),
+                _ => return self.error("invalid function argument"),
+            };
+            args.push(arg);
+        }
+        self.blank();
+        self.expect(")")?;
+        #[cfg(feature = "regex")]
+        let regex = match (function, args.get(1)) {
+            (Function::Match | Function::Search, Some(Argument::Literal(Value::String(pattern)))) => {
+                Some(iregexp(pattern, function == Function::Match))
+            }
+            _ => None,
+        };
+        Ok(FunctionExpr {
+            function,
+            args,
+            #[cfg(feature = "regex")]
+            regex,
+        })
+    }
+}
+
+impl Query {
+    /// Whether the query selects at most one node
+    fn is_singular(&self) -> bool {
+        self.segments.iter().all(|s| match s {
+            Segment::Child(selectors) => {
+                matches!(selectors.as_slice(), [Selector::Name(_)] | [Selector::Index(_)])
+            }
+            Segment::Descendant(_) => false,
+        })
+    }
+
+    fn select<'a>(&self, node: Node<'a>, root: &'a Value) -> Vec<Node<'a>> {
+        let mut nodes = vec![node];
+        for segment in &self.segments {
+            let mut next = vec![];
+            for node in nodes {
+                match segment {
+                    Segment::Child(selectors) => {
+                        for selector in selectors {
+                            selector.select(&node, root, &mut next);
+                        }
+                    }
+                    Segment::Descendant(selectors) => descend(node, selectors, root, &mut next),
+                }
+            }
+            nodes = next;
+        }
+        nodes
+    }
+}
+
+/// Apply the selectors to the node and all its descendants, parents first
+fn descend<'a>(node: Node<'a>, selectors: &[Selector], root: &'a Value, res: &mut Vec<Node<'a>>) {
+    for selector in selectors {
+        selector.select(&node, root, res);
+    }
+    for child in children(&node) {
+        descend(child, selectors, root, res);
+    }
+}
+
+fn children<'a>(node: &Node<'a>) -> Vec<Node<'a>> {
+    let child = |loc: Location| {
+        let mut path = node.0.clone();
+        path.push(loc);
+        path
+    };
+    match node.1 {
+        Value::Array(arr) => arr
+            .iter()
+            .enumerate()
+            .map(|(i, v)| (child(Location::Index(i)), v))
+            .collect(),
+        Value::Object(obj) => obj
+            .iter()
+            .map(|(k, v)| (child(Location::Key(k.clone())), v))
+            .collect(),
+        _ => vec![],
+    }
+}
+
+impl Selector {
+    fn select<'a>(&self, node: &Node<'a>, root: &'a Value, res: &mut Vec<Node<'a>>) {
+        let child = |loc: Location| {
+            let mut path = node.0.clone();
+            path.push(loc);
+            path
+        };
+        match (self, node.1) {
+            (Selector::Name(name), Value::Object(obj)) => {
+                if let Some(v) = obj.get(name) {
+                    res.push((child(Location::Key(name.clone())), v));
+                }
+            }
+            (Selector::Wildcard, _) => res.extend(children(node)),
+            (Selector::Index(i), Value::Array(arr)) => {
+                let len = arr.len() as i64;
+                let i = if *i < 0 { len + i } else { *i };
+                if (0..len).contains(&i) {
+                    res.push((child(Location::Index(i as usize)), &arr[i as usize]));
+                }
+            }
+            (Selector::Slice(start, end, step), Value::Array(arr)) => {
+                for i in slice_indices(arr.len() as i64, *start, *end, step.unwrap_or(1)) {
+                    res.push((child(Location::Index(i)), &arr[i]));
+                }
+            }
+            (Selector::Filter(expr), Value::Array(_) | Value::Object(_)) => {
+                for c in children(node) {
+                    if expr.test(c.1, root) {
+                        res.push(c);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Indices selected by a slice, as specified in section 2.3.4.2.2 of RFC 9535
+fn slice_indices(len: i64, start: Option<i64>, end: Option<i64>, step: i64) -> Vec<usize> {
+    let normalize = |i: i64| if i >= 0 { i } else { len + i };
+    let mut res = vec![];
+    if step > 0 {
+        let lower = normalize(start.unwrap_or(0)).max(0).min(len);
+        let upper = normalize(end.unwrap_or(len)).max(0).min(len);
+        let mut i = lower;
+        while i < upper {
+            res.push(i as usize);
+            i += step;
+        }
+    } else if step < 0 {
+        let upper = normalize(start.unwrap_or(len - 1)).max(-1).min(len - 1);
+        let lower = end.map_or(-1, |e| normalize(e).max(-1).min(len - 1));
+        let mut i = upper;
+        while lower < i {
+            res.push(i as usize);
+            i += step;
+        }
+    }
+    res
+}
+
+impl Logical {
+    fn test(&self, current: &Value, root: &Value) -> bool {
+        match self {
+            Logical::Or(exprs) => exprs.iter().any(|e| e.test(current, root)),
+            Logical::And(exprs) => exprs.iter().all(|e| e.test(current, root)),
+            Logical::Not(expr) => !expr.test(current, root),
+            Logical::Exists(q) => !q.select(current, root).is_empty(),
+            Logical::Function(f) => matches!(f.call(current, root), Evaluated::Logical(true)),
+            Logical::Comparison(left, op, right) => {
+                let (l, r) = (left.value(current, root), right.value(current, root));
+                let (l, r) = (l.as_deref(), r.as_deref());
+                match op {
+                    Op::Eq => equal(l, r),
+                    Op::Ne => !equal(l, r),
+                    Op::Lt => less(l, r),
+                    Op::Le => less(l, r) || equal(l, r),
+                    Op::Gt => less(r, l),
+                    Op::Ge => less(r, l) || equal(l, r),
+                }
+            }
+        }
+    }
+}
+
+fn equal(l: Option<&Value>, r: Option<&Value>) -> bool {
+    match (l, r) {
+        (Some(l), Some(r)) => Compare {
+            numbers: NumberCompare::Semantic,
+            ..Compare::default()
+        }
+        .eq(l, r),
+        (None, None) => true,
+        _ => false,
+    }
+}
+
+fn less(l: Option<&Value>, r: Option<&Value>) -> bool {
+    match (l, r) {
+        (Some(Value::Number(l)), Some(Value::Number(r))) => {
+            l.as_f64().partial_cmp(&r.as_f64()) == Some(Ordering::Less)
+        }
+        (Some(Value::String(l)), Some(Value::String(r))) => l < r,
+        _ => false,
+    }
+}
+
+impl FilterQuery {
+    fn select<'a>(&self, current: &'a Value, root: &'a Value) -> Vec<&'a Value> {
+        let start = if self.relative { current } else { root };
+        self.query
+            .select((vec![], start), root)
+            .into_iter()
+            .map(|n| n.1)
+            .collect()
+    }
+}
+
+impl Comparable {
+    fn value<'a>(&self, current: &'a Value, root: &'a Value) -> Option<Cow<'a, Value>> {
+        match self {
+            Comparable::Literal(v) => Some(Cow::Owned(v.clone())),
+            Comparable::Query(q) => q.select(current, root).first().map(|v| Cow::Borrowed(*v)),
+            Comparable::Function(f) => match f.call(current, root) {
+                Evaluated::Value(v) => v,
+                _ => None,
+            },
+        }
+    }
+}
+
+impl FunctionExpr {
+    fn call<'a>(&self, current: &'a Value, root: &'a Value) -> Evaluated<'a> {
+        let args = self
+            .args
+            .iter()
+            .map(|a| match a {
+                Argument::Literal(v) => Evaluated::Value(Some(Cow::Owned(v.clone()))),
+                Argument::Query(q) if self.function.signature().0[0] == Type::Nodes => {
+                    Evaluated::Nodes(q.select(current, root))
+                }
+                Argument::Query(q) => Evaluated::Value(q.select(current, root).first().map(|v| Cow::Borrowed(*v))),
+                Argument::Function(f) => f.call(current, root),
+            })
+            .collect::<Vec<Evaluated>>();
+        match self.function {
+            Function::Length => Evaluated::Value(match &args[0] {
+                Evaluated::Value(Some(v)) => match v.as_ref() {
+                    Value::String(s) => Some(s.chars().count()),
+                    Value::Array(a) => Some(a.len()),
+                    Value::Object(o) => Some(o.len()),
+                    _ => None,
+                },
+                _ => None,
+            }
+            .map(|n| Cow::Owned(Value::from(n)))),
+            Function::Count => match &args[0] {
+                Evaluated::Nodes(nodes) => Evaluated::Value(Some(Cow::Owned(Value::from(nodes.len())))),
+                _ => Evaluated::Value(None),
+            },
+            Function::Value => match &args[0] {
+                Evaluated::Nodes(nodes) if nodes.len() == 1 => Evaluated::Value(Some(Cow::Borrowed(nodes[0]))),
+                _ => Evaluated::Value(None),
+            },
+            #[cfg(feature = "regex")]
+            Function::Match | Function::Search => {
+                let anchored = self.function == Function::Match;
+                let string = |i: usize| match &args[i] {
+                    Evaluated::Value(Some(v)) => v.as_str().map(|s| s.to_string()),
+                    _ => None,
+                };
+                Evaluated::Logical(match (string(0), &self.regex) {
+                    (Some(s), Some(re)) => re.as_ref().map_or(false, |re| re.is_match(&s)),
+                    (Some(s), None) => match string(1) {
+                        Some(pattern) => iregexp(&pattern, anchored).map_or(false, |re| re.is_match(&s)),
+                        None => false,
+                    },
+                    _ => false,
+                })
+            }
+        }
+    }
+}
+
+/// Compiles an I-Regexp (RFC 9485), in which `.` matches any character but line breaks
+#[cfg(feature = "regex")]
+fn iregexp(pattern: &str, anchored: bool) -> Option<Regex> {
+    let mut re = String::with_capacity(pattern.len() + 8);
+    if anchored {
+        re.push_str("^(?:");
+    }
+    let mut chars = pattern.chars();
+    let mut in_class = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                re.push(c);
+                re.push(chars.next()?);
+            }
+            '[' => {
+                in_class = true;
+                re.push(c);
+            }
+            ']' => {
+                in_class = false;
+                re.push(c);
+            }
+            '.' if !in_class => re.push_str("[^\\n\\r]"),
+            _ => re.push(c),
+        }
+    }
+    if anchored {
+        re.push_str(")$");
+    }
+    Regex::new(&re).ok()
+}
+
+/// Normalized path of a location, as defined in section 2.7 of RFC 9535
+fn normalized_path(location: &[Location]) -> String {
+    let mut res = "$".to_string();
+    for loc in location {
+        match loc {
+            Location::Index(i) => {
+                let _ = write!(res, "[{}]", i);
+            }
+            Location::Key(k) => {
+                res.push_str("['");
+                for c in k.chars() {
+                    match c {
+                        '\u{8}' => res.push_str("\\b"),
+                        '\u{c}' => res.push_str("\\f"),
+                        '\n' => res.push_str("\\n"),
+                        '\r' => res.push_str("\\r"),
+                        '\t' => res.push_str("\\t"),
+                        '\'' => res.push_str("\\'"),
+                        '\\' => res.push_str("\\\\"),
+                        '\u{0}'..='\u{1f}' => {
+                            let _ = write!(res, "\\u{:04x}", c as u32);
+                        }
+                        _ => res.push(c),
+                    }
+                }
+                res.push_str("']");
+            }
+        }
+    }
+    res
+}
+
+fn parse(query: &str) -> Result<Query> {
+    Parser { src: query, pos: 0, depth: 0 }.query()
+}
+
+/// Nodes selected by the query, with their locations
+pub(crate) fn locate<'a>(root: &'a Value, query: &str) -> Result<Vec<Node<'a>>> {
+    Ok(parse(query)?.select((vec![], root), root))
+}
+
+/// Nodes selected by the query, with their normalized paths
+pub(crate) fn query<'a>(root: &'a Value, query: &str) -> Result<Vec<QueryMatch<'a>>> {
+    Ok(locate(root, query)?
+        .into_iter()
+        .map(|(loc, value)| QueryMatch {
+            path: normalized_path(&loc),
+            value,
+        })
+        .collect())
+}
+
+/// Calls `f` with the normalized path and the value of every node selected by the query,
+/// and returns the number of nodes. Deeper nodes are visited first, so that `f` only changes
+/// nodes already visited and the locations of the others stay valid.
+pub(crate) fn query_mut<F: FnMut(&str, &mut Value)>(root: &mut Value, query: &str, mut f: F) -> Result<usize> {
+    let mut locations = locate(root, query)?
+        .into_iter()
+        .map(|(loc, _)| loc)
+        .collect::<Vec<Vec<Location>>>();
+    // stable, so nodes at the same depth keep the order of the query
+    locations.sort_by_key(|loc| std::cmp::Reverse(loc.len()));
+    let mut count = 0;
+    for loc in locations {
+        let mut val = Some(&mut *root);
+        for l in &loc {
+            val = val.and_then(|v| match (l, v) {
+                (Location::Key(k), Value::Object(obj)) => obj.get_mut(k),
+                (Location::Index(i), Value::Array(arr)) => arr.get_mut(*i),
+                _ => None,
+            });
+        }
+        if let Some(v) = val {
+            f(&normalized_path(&loc), v);
+            count += 1;
+        }
+    }
+    Ok(count)
+}
@@ -0,0 +1,163 @@
+use crate::{
+    error::{Error, Result},
+    pointer,
+};
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Moves of values between JSON pointers, applied by [`JsonUtils::remap`](crate::JsonUtils::remap).
+///
+/// In source pointers a `*` token matches any single key or index, and the `*` tokens of the
+/// destination are replaced, in order, by the keys and indices they matched. Missing objects
+/// and arrays on the way to destinations are created like in [`JsonUtils::set_path`](crate::JsonUtils::set_path),
+/// except that keys matched in objects create objects even when they are made of digits.
+/// Two values moved to the same destination, unless appended with `-`, are a [`Error::KeyCollision`].
+#[derive(Clone, Debug, Default)]
+pub struct Mapping {
+    pub(crate) moves: Vec<(String, String)>,
+}
+
+impl Mapping {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a move of the values at `from` to `to`. Moves are applied in the order they are added.
+    pub fn map(mut self, from: &str, to: &str) -> Self {
+        self.moves.push((from.to_string(), to.to_string()));
+        self
+    }
+}
+
+impl<'a> FromIterator<(&'a str, &'a str)> for Mapping {
+    fn from_iter<I: IntoIterator<Item = (&'a str, &'a str)>>(iter: I) -> Self {
+        iter.into_iter().fold(Mapping::new(), |m, (from, to)| m.map(from, to))
+    }
+}
+
+/// Apply all the moves of the mapping and return the source paths that were not found
+pub(crate) fn remap(root: &mut Value, mapping: &Mapping) -> Result<Vec<String>> {
+    let mut unmatched = vec![];
+    // sources of the destinations, to report two values moved to the same place
+    let mut sources: HashMap<String, String> = HashMap::new();
+    for (from, to) in &mapping.moves {
+        let pattern = pointer::parse(from)?;
+        let target = pointer::parse(to)?;
+        let mut found = vec![];
+        locate(root, &pattern, "", vec![], &mut found, &mut unmatched);
+        let mut moves = vec![];
+        for (path, captures) in found {
+            let mut captures = captures.into_iter();
+            let mut dest = vec![];
+            // whether the tokens of the destination are object keys, even if made of digits
+            let mut keys = vec![];
+            for token in &target {
+                let (token, key) = if token == "*" {
+                    captures.next().ok_or_else(|| Error::InvalidPointer { path: to.clone() })?
+                } else {
+                    (token.clone(), false)
+                };
+                dest.push(token);
+                keys.push(key);
+            }
+            let dest_path = dest.iter().fold(String::new(), |p, t| pointer::push(&p, t));
+            // appended values never replace each other
+            if !dest.iter().any(|t| t == "-") {
+                if let Some(first) = sources.insert(dest_path.clone(), path.clone()) {
+                    return Err(Error::KeyCollision { path: dest_path, first, second: path });
+                }
+            }
+            moves.push((path, dest, keys, dest_path));
+        }
+        // later siblings are taken first so that the indices of the earlier ones stay valid
+        let values = moves
+            .iter()
+            .rev()
+            .map(|(path, ..)| pointer::remove(root, path))
+            .collect::<Result<Vec<Value>>>()?;
+        for ((_, dest, keys, dest_path), value) in moves.iter().zip(values.into_iter().rev()) {
+            check_arrays(root, dest)?;
+            pointer::set_tokens(root, dest, keys, dest_path, value, false)?;
+        }
+    }
+    Ok(unmatched)
+}
+
+/// Fail if a key of the destination is not an index of an existing array on the way
+fn check_arrays(root: &Value, dest: &[String]) -> Result<()> {
+    let mut val = root;
+    let mut path = String::new();
+    for token in dest {
+        val = match val {
+            Value::Object(obj) => match obj.get(token) {
+                Some(v) => v,
+                None => return Ok(()),
+            },
+            Value::Array(arr) => match pointer::index(token, "") {
+                Ok(i) => match arr.get(i) {
+                    Some(v) => v,
+                    None => return Ok(()),
+                },
+                Err(_) if token == "-" => return Ok(()),
+                Err(_) => {
+                    return Err(Error::ShapeMismatch {
+                        path,
+                        expected: "object",
+                        found: "array",
+                    })
+                }
+            },
+            _ => return Ok(()),
+        };
+        path = pointer::push(&path, token);
+    }
+    Ok(())
+}
+
+/// Find the paths matching the tokens, with the keys and indices matched by `*` tokens and
+/// whether they are object keys, and the paths at which no value matches, completed with
+/// the rest of the tokens
+fn locate(
+    val: &Value,
+    tokens: &[String],
+    path: &str,
+    captures: Vec<(String, bool)>,
+    res: &mut Vec<(String, Vec<(String, bool)>)>,
+    unmatched: &mut Vec<String>,
+) {
+    let (token, rest) = match tokens.split_first() {
+        Some(split) => split,
+        None => {
+            res.push((path.to_string(), captures));
+            return;
+        }
+    };
+    let object = val.is_object();
+    let mut visit = |key: String, child: &Value| {
+        let mut captures = captures.clone();
+        if token == "*" {
+            captures.push((key.clone(), object));
+        }
+        locate(child, rest, &pointer::push(path, &key), captures, res, unmatched);
+    };
+    let visited = match val {
+        Value::Object(obj) if token == "*" => {
+            obj.iter().for_each(|(k, v)| visit(k.clone(), v));
+            !obj.is_empty()
+        }
+        Value::Array(arr) if token == "*" => {
+            arr.iter().enumerate().for_each(|(i, v)| visit(i.to_string(), v));
+            !arr.is_empty()
+        }
+        Value::Object(obj) => obj.get(token).map(|v| visit(token.clone(), v)).is_some(),
+        Value::Array(arr) => pointer::index(token, path)
+            .ok()
+            .and_then(|i| arr.get(i))
+            .map(|v| visit(token.clone(), v))
+            .is_some(),
+        _ => false,
+    };
+    if !visited {
+        unmatched.push(tokens.iter().fold(path.to_string(), |p, t| pointer::push(&p, t)));
+    }
+}
@@ -0,0 +1,143 @@
+use crate::{
+    error::{Error, Result},
+    pointer::{self, PathPattern},
+};
+use serde_json::Value::{self, *};
+use std::collections::HashMap;
+
+/// Case of the keys produced by [`JsonUtils::rename_keys`](crate::JsonUtils::rename_keys).
+///
+/// Keys are split into words at underscores, dashes, whitespace and lowercase to uppercase
+/// transitions, so `fooBar`, `FooBar`, `foo-bar` and `FOO_BAR` are all made of the words
+/// `foo` and `bar`, and `HTTPServer` of `http` and `server`. Leading and trailing underscores are kept.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum Case {
+    /// `foo_bar`
+    #[default]
+    Snake,
+    /// `fooBar`
+    Camel,
+    /// `FooBar`
+    Pascal,
+    /// `foo-bar`
+    Kebab,
+    /// `FOO_BAR`
+    ScreamingSnake,
+}
+
+impl Case {
+    /// Convert a key to this case
+    pub(crate) fn convert(self, key: &str) -> std::string::String {
+        let inner = key.trim_matches('_');
+        if inner.is_empty() {
+            return key.to_string();
+        }
+        let start = key.len() - key.trim_start_matches('_').len();
+        let end = start + inner.len();
+        let words = words(inner);
+        let capitalize = |w: &str| {
+            let mut chars = w.chars();
+            chars.next().map_or_else(std::string::String::new, |c| {
+                c.to_uppercase().chain(chars.flat_map(char::to_lowercase)).collect()
+            })
+        };
+        let converted = match self {
+            Case::Snake => words.iter().map(|w| w.to_lowercase()).collect::<Vec<_>>().join("_"),
+            Case::Kebab => words.iter().map(|w| w.to_lowercase()).collect::<Vec<_>>().join("-"),
+            Case::ScreamingSnake => words.iter().map(|w| w.to_uppercase()).collect::<Vec<_>>().join("_"),
+            Case::Pascal => words.iter().map(|w| capitalize(w)).collect(),
+            Case::Camel => words
+                .iter()
+                .enumerate()
+                .map(|(i, w)| if i == 0 { w.to_lowercase() } else { capitalize(w) })
+                .collect(),
+        };
+        format!("{}{}{}", &key[..start], converted, &key[end..])
+    }
+}
+
+/// Split a key into words
+fn words(key: &str) -> Vec<std::string::String> {
+    let chars = key.chars().collect::<Vec<char>>();
+    let mut words = vec![];
+    let mut word = std::string::String::new();
+    for (i, &c) in chars.iter().enumerate() {
+        if c == '_' || c == '-' || c.is_whitespace() {
+            if !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            continue;
+        }
+        if c.is_uppercase() && !word.is_empty() {
+            let prev = chars[i - 1];
+            let next_lower = chars.get(i + 1).map_or(false, |n| n.is_lowercase());
+            if prev.is_lowercase() || prev.is_ascii_digit() || (prev.is_uppercase() && next_lower) {
+                words.push(std::mem::take(&mut word));
+            }
+        }
+        word.push(c);
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Options for [`JsonUtils::rename_keys_with`](crate::JsonUtils::rename_keys_with).
+#[derive(Clone, Debug, Default)]
+pub struct RenameOptions {
+    pub(crate) case: Case,
+    pub(crate) skip: Vec<PathPattern>,
+}
+
+impl RenameOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the case of the renamed keys, `Case::Snake` by default.
+    pub fn case(mut self, case: Case) -> Self {
+        self.case = case;
+        self
+    }
+
+    /// Leaves alone the objects at JSON pointers matching the pattern and everything they contain.
+    /// Pointers use the original keys, and in patterns a `*` token matches any single key or index
+    /// and a `**` token matches any number of them. Panics if the pattern is not a JSON pointer.
+    pub fn skip(mut self, pattern: &str) -> Self {
+        self.skip.push(PathPattern::new(pattern));
+        self
+    }
+}
+
+/// Rename the keys of all objects out of the skipped paths
+pub(crate) fn rename_keys(val: &mut Value, options: &RenameOptions, path: &str) -> Result<()> {
+    match val {
+        Object(obj) => {
+            if options.skip.iter().any(|p| p.matches(path)) {
+                return Ok(());
+            }
+            // original key of every renamed key
+            let mut origins: HashMap<std::string::String, std::string::String> = HashMap::new();
+            for (k, mut v) in std::mem::take(obj) {
+                rename_keys(&mut v, options, &pointer::push(path, &k))?;
+                let name = options.case.convert(&k);
+                if let Some(first) = origins.insert(name.clone(), k.clone()) {
+                    return Err(Error::KeyCollision {
+                        path: pointer::push(path, &name),
+                        first,
+                        second: k,
+                    });
+                }
+                obj.insert(name, v);
+            }
+        }
+        Array(arr) => {
+            for (i, v) in arr.iter_mut().enumerate() {
+                rename_keys(v, options, &pointer::push(path, &i.to_string()))?;
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
@@ -1,6 +1,14 @@
-use serde_json::{from_str, json, Value};
+use serde_json::{from_str, from_value, json, Value};
 use serde::{Deserialize, Serialize};
-use crate::{JsonUtils, merge_similar_objects};
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+};
+use crate::{
+    create_merge_patch, ArrayCompare, Case, merge::merge_similar_objects, patch::{diff, PatchOperation}, utils::{Compare, DedupeHashValue, HashValue}, ArrayMerge, Conflict, ArrayIndexStyle, DiffOptions, DiffReport, Difference, DedupOptions, DedupReport, DedupScope, Error,
+    Fingerprint, FingerprintOptions, FlattenOptions, JsonUtils, Keep, Kept, Mapping, MergeOptions, MergeSimilarOptions, NumberCompare, Placement, PruneOptions, Removed, RenameOptions, StringCompare,
+    MAX_DEPTH,
+};
 
 const DATA: &str = r###"
     [
@@ -234,9 +242,9 @@ fn test_merge_similar_objects() {
     let src2: Value = from_str(MERGE_SRC2).unwrap();
     let src11: Value = from_str(MERGE_SRC1).unwrap();
     let ress1: Value = from_str(MERGE_RES1).unwrap();
-    let res1 = merge_similar_objects(&src1, &src11).unwrap();
+    let res1 = merge_similar_objects(&src1, &src11, &Compare::default()).unwrap();
     assert_eq!(res1, src1);
-    let res2 = merge_similar_objects(&src1, &src2).unwrap();
+    let res2 = merge_similar_objects(&src1, &src2, &Compare::default()).unwrap();
     assert_eq!(res2, ress1);
 }
 
@@ -295,3 +303,1023 @@ fn test_extend(){
     src4.extend(json!({"year": 2019i32}));
     assert_eq!(src4, src5);
 }
+
+#[test]
+fn test_try_variants() {
+    let src1: Value = from_str(TO_STRUCT).unwrap();
+    let car = src1.try_to_struct::<Car>().unwrap();
+    assert_eq!(car.year, 2019);
+    let err = json!({"model": 1}).try_to_struct::<Car>().unwrap_err();
+    assert!(matches!(err, Error::Deserialize { .. }));
+
+    let mut src2: Value = from_str(EXTEND).unwrap();
+    assert!(matches!(src2.try_extend(json!(1)), Err(Error::ShapeMismatch { found: "number", .. })));
+    let mut src3 = json!("scalar");
+    let err = src3.try_extend(json!({})).unwrap_err();
+    assert!(matches!(err, Error::ShapeMismatch { found: "string", .. }));
+    assert_eq!(err.to_string(), "(root): expected object or array, found string");
+
+    let mut deep = json!({"leaf": null});
+    for _ in 0..MAX_DEPTH {
+        deep = json!({"a": deep});
+    }
+    match deep.try_skip_null() {
+        Err(Error::LimitExceeded { path, limit, .. }) => {
+            assert_eq!(limit, MAX_DEPTH);
+            assert_eq!(path, "/a".repeat(MAX_DEPTH));
+        }
+        r => panic!("unexpected result: {:?}", r),
+    }
+
+    let err = merge_similar_objects(&json!({"a": 1}), &json!({"b": 1}), &Compare::default()).unwrap_err();
+    assert!(matches!(err, Error::IncompatibleMerge { .. }));
+}
+
+#[test]
+fn test_try_to_struct_path() {
+    let mut src: Value = from_str(TO_STRUCT2).unwrap();
+    src[1]["year"] = json!("2020");
+    match src.try_to_struct::<Vec<Car>>() {
+        Err(Error::Deserialize { path, message }) => {
+            assert_eq!(path, "/1/year");
+            assert_eq!(message, "invalid type: string \"2020\", expected i32");
+        }
+        r => panic!("unexpected result: {:?}", r),
+    }
+
+    let src = json!({"a/b": {"model": "car model", "make": "car make"}});
+    match src.try_to_struct::<std::collections::BTreeMap<String, Car>>() {
+        Err(Error::Deserialize { path, message }) => {
+            assert_eq!(path, "/a~1b");
+            assert_eq!(message, "missing field `year`");
+        }
+        r => panic!("unexpected result: {:?}", r),
+    }
+}
+
+#[test]
+fn test_merge_similar_order() {
+    let src = json!([
+        "b",
+        {"key1": "value in here", "key2": "asas1"},
+        "a",
+        {"other": 1},
+        {"key1": "value in here", "key2": "asas2"},
+        "b"
+    ]);
+    let mut first = src.clone();
+    first.merge_similar();
+    assert_eq!(first, json!([
+        "b",
+        {"key1": "value in here", "key2": ["asas1", "asas2"]},
+        "a",
+        {"other": 1}
+    ]));
+
+    let mut last = src;
+    last.merge_similar_with(&MergeSimilarOptions::new().placement(Placement::Last));
+    assert_eq!(last, json!([
+        "a",
+        {"other": 1},
+        {"key1": "value in here", "key2": ["asas1", "asas2"]},
+        "b"
+    ]));
+}
+
+#[test]
+fn test_dedup_strings() {
+    let src = json!([
+        {"city": "Straße", "tags": ["a  b", "A B"]},
+        {"city": "STRASSE", "tags": ["a b"]},
+        {"city": "straße", "tags": ["A B"]}
+    ]);
+
+    let mut val = src.clone();
+    val.dedup();
+    assert_eq!(val, src);
+
+    let mut val = src.clone();
+    val.dedup_with(&DedupOptions::new().strings(StringCompare::CaseInsensitive));
+    assert_eq!(val, json!([
+        {"city": "Straße", "tags": ["a  b", "A B"]},
+        {"city": "STRASSE", "tags": ["a b"]},
+        {"city": "straße", "tags": ["A B"]}
+    ]));
+
+    let ascii = json!([{"name": "Alice", "tags": ["X", "x"]}, {"name": "ALICE", "tags": ["x"]}, "Bob", "bob"]);
+    let mut val = ascii.clone();
+    val.dedup();
+    assert_eq!(val, ascii);
+    let mut val = ascii;
+    val.dedup_with(&DedupOptions::new().strings(StringCompare::CaseInsensitive));
+    assert_eq!(val, json!([{"name": "Alice", "tags": ["X"]}, "Bob"]));
+
+    let mut val = src;
+    val.dedup_with(
+        &DedupOptions::new()
+            .strings(StringCompare::CaseFolded)
+            .normalize_whitespace(true),
+    );
+    assert_eq!(val, json!([{"city": "Straße", "tags": ["a  b"]}]));
+
+    let mut val = json!(["STRAẞE", "strasse", "ﬁle", "FILE", "ὈΔΥΣΣΕΎΣ", "ὀδυσσεύς", "Ꮳ", "ꮳ"]);
+    val.dedup_with(&DedupOptions::new().strings(StringCompare::CaseFolded));
+    assert_eq!(val, json!(["STRAẞE", "ﬁle", "ὈΔΥΣΣΕΎΣ", "Ꮳ"]));
+}
+
+#[test]
+fn test_dedup_by_keys() {
+    let src = json!([
+        {"id": 1, "source": {"host": "a"}, "ts": 10},
+        {"id": 1, "source": {"host": "b"}, "ts": 11},
+        {"id": 1, "ts": 12},
+        {"id": 1, "source": {"host": "a"}, "ts": 13},
+        {"id": 1, "source": {"host": "a"}, "ts": 14, "extra": true}
+    ]);
+    let keys = ["/id", "/source/host"];
+
+    let mut first = src.clone();
+    first.dedup_by_keys(&keys, Keep::First);
+    assert_eq!(first, json!([
+        {"id": 1, "source": {"host": "a"}, "ts": 10},
+        {"id": 1, "source": {"host": "b"}, "ts": 11},
+        {"id": 1, "ts": 12}
+    ]));
+
+    let mut last = src.clone();
+    last.dedup_by_keys(&keys, Keep::Last);
+    assert_eq!(last, json!([
+        {"id": 1, "source": {"host": "b"}, "ts": 11},
+        {"id": 1, "ts": 12},
+        {"id": 1, "source": {"host": "a"}, "ts": 14, "extra": true}
+    ]));
+
+    let mut merged = src;
+    merged.dedup_by_keys(&keys, Keep::Merge);
+    assert_eq!(merged, json!([
+        {"id": 1, "source": {"host": "a"}, "ts": [10, 13]},
+        {"id": 1, "source": {"host": "b"}, "ts": 11},
+        {"id": 1, "ts": 12},
+        {"id": 1, "source": {"host": "a"}, "ts": 14, "extra": true}
+    ]));
+
+    let mut none = json!([{"id": 1}, {"id": 2}, 3]);
+    none.dedup_by_keys(&[], Keep::First);
+    assert_eq!(none, json!([{"id": 1}, {"id": 2}, 3]));
+}
+
+#[test]
+fn test_dedup_scope() {
+    let src = json!([
+        {"samples": [1, 1, 2], "nested": {"tags": ["a", "a"]}},
+        {"samples": [1, 1, 2], "nested": {"tags": ["a", "a"]}}
+    ]);
+
+    let mut val = src.clone();
+    val.dedup_with(&DedupOptions::new().scope(DedupScope::TopLevel));
+    assert_eq!(val, json!([{"samples": [1, 1, 2], "nested": {"tags": ["a", "a"]}}]));
+
+    let mut val = src.clone();
+    val.dedup_with(&DedupOptions::new().scope(DedupScope::MaxDepth(2)));
+    assert_eq!(val, json!([{"samples": [1, 2], "nested": {"tags": ["a", "a"]}}]));
+
+    let mut val = src.clone();
+    val.dedup_with(&DedupOptions::new().scope(DedupScope::Paths(vec!["/*/nested/tags".to_string()])));
+    assert_eq!(val, json!([
+        {"samples": [1, 1, 2], "nested": {"tags": ["a"]}},
+        {"samples": [1, 1, 2], "nested": {"tags": ["a"]}}
+    ]));
+
+    let mut val = src;
+    val.dedup_with(&DedupOptions::new().scope(DedupScope::Paths(vec!["/**/tags".to_string(), "".to_string()])));
+    assert_eq!(val, json!([{"samples": [1, 1, 2], "nested": {"tags": ["a"]}}]));
+}
+
+#[test]
+fn test_dedup_with_counts() {
+    let mut val: Value = from_str(DATA).unwrap();
+    let result: Value = from_str(RESULT_DEDUP).unwrap();
+    let report = val.dedup_with_counts(&DedupOptions::new());
+    assert_eq!(result, val);
+    assert_eq!(report, vec![
+        DedupReport { path: "/0/key4".to_string(), kept: vec![] },
+        DedupReport {
+            path: "/0/key5".to_string(),
+            kept: vec![
+                Kept { index: 0, duplicates: vec![] },
+                Kept { index: 1, duplicates: vec![] },
+                Kept { index: 2, duplicates: vec![3] },
+            ],
+        },
+        DedupReport {
+            path: "".to_string(),
+            kept: vec![
+                Kept { index: 0, duplicates: vec![] },
+                Kept { index: 1, duplicates: vec![2] },
+            ],
+        },
+    ]);
+}
+
+#[test]
+fn test_numeric_equivalence() {
+    let src: Value = from_str(r#"[1, 1.0, 1e0, -0.0, 0, 1.5, 18446744073709551615, 1.8446744073709552e19]"#).unwrap();
+
+    let mut val = src.clone();
+    val.dedup();
+    assert_eq!(val.as_array().unwrap().len(), 7);
+
+    let mut val = src.clone();
+    val.dedup_with(&DedupOptions::new().numbers(NumberCompare::Semantic));
+    assert_eq!(val, from_str::<Value>("[1, -0.0, 1.5, 18446744073709551615, 1.8446744073709552e19]").unwrap());
+
+    let mut val = json!([1.0, 1.0000001, 1.1]);
+    val.dedup_with(&DedupOptions::new().epsilon(1e-6));
+    assert_eq!(val, json!([1.0, 1.1]));
+
+    let mut val = json!([{"key1": "a", "key2": 1}, {"key1": "a", "key2": 1.0}, 2, 2.0]);
+    val.merge_similar_with(&MergeSimilarOptions::new().numbers(NumberCompare::Semantic));
+    assert_eq!(val, json!([{"key1": "a", "key2": 1}, 2]));
+}
+
+#[test]
+fn test_prune_with() {
+    let src = json!({
+        "a": {"b": null},
+        "c": ["", "  ", "x", 0, 0.0, 1, false, true],
+        "d": [[null]],
+        "e": {}
+    });
+
+    let mut val = src.clone();
+    val.prune_with(&PruneOptions::new());
+    assert_eq!(val, json!({
+        "a": {},
+        "c": ["", "  ", "x", 0, 0.0, 1, false, true],
+        "d": [[]],
+        "e": {}
+    }));
+
+    let mut val = src.clone();
+    val.skip_null_and_empty();
+    assert_eq!(val, json!({"a": {}, "c": ["", "  ", "x", 0, 0.0, 1, false, true], "d": [[]]}));
+
+    let mut val = src.clone();
+    val.prune_with(&PruneOptions::null_and_empty().cascade(true));
+    assert_eq!(val, json!({"c": ["", "  ", "x", 0, 0.0, 1, false, true]}));
+
+    let mut val = src;
+    val.prune_with(
+        &PruneOptions::new()
+            .nulls(false)
+            .empty_strings(true)
+            .falses(true)
+            .zeros(true),
+    );
+    assert_eq!(val, json!({"a": {"b": null}, "c": ["  ", "x", 1, true], "d": [[null]], "e": {}}));
+}
+
+#[test]
+fn test_prune() {
+    let mut val = json!({
+        "key1": "N/A",
+        "key2": ["foo", "-", {"a/b": "-", "c": 1}],
+        "key3": {"key1": null}
+    });
+    let mut paths = vec![];
+    val.prune(|path, v| {
+        paths.push(path.to_string());
+        v == "N/A" || v == "-" || path == "/key3"
+    });
+    assert_eq!(val, json!({"key2": ["foo", {"c": 1}]}));
+    assert_eq!(paths, vec![
+        "/key1",
+        "/key2",
+        "/key2/0",
+        "/key2/1",
+        "/key2/2",
+        "/key2/2/a~1b",
+        "/key2/2/c",
+        "/key3"
+    ]);
+}
+
+#[test]
+fn test_prune_report() {
+    let mut val: Value = from_str(DATA).unwrap();
+    let result: Value = from_str(RESULT_SKIP_NULL_AND_EMPTY).unwrap();
+    let removed = val.skip_null_and_empty_with_report(true);
+    assert_eq!(result, val);
+    assert_eq!(removed, vec![
+        Removed { path: "/0/key1".to_string(), value: Some(Value::Null) },
+        Removed { path: "/0/key3".to_string(), value: Some(json!({})) },
+        Removed { path: "/0/key4".to_string(), value: Some(json!([])) },
+        Removed { path: "/1/key2".to_string(), value: Some(Value::Null) },
+        Removed { path: "/2/key2".to_string(), value: Some(Value::Null) },
+    ]);
+
+    let mut val = json!({"a": {"b": [null, 1, null]}, "c": {"d": null}});
+    let removed = val.prune_with_report(&PruneOptions::null_and_empty().cascade(true), false);
+    assert_eq!(val, json!({"a": {"b": [1]}}));
+    let paths: Vec<&str> = removed.iter().map(|r| r.path.as_str()).collect();
+    assert_eq!(paths, vec!["/a/b/0", "/a/b/2", "/c/d", "/c"]);
+}
+
+#[test]
+fn test_prune_paths() {
+    let src = json!({
+        "name": null,
+        "patch": {"email": null, "address": {"city": null}},
+        "items": [{"id": null, "tags": null}, {"id": 1, "tags": null}]
+    });
+
+    let mut val = src.clone();
+    val.prune_with(&PruneOptions::new().exclude("/patch/**"));
+    assert_eq!(val, json!({
+        "patch": {"email": null, "address": {"city": null}},
+        "items": [{}, {"id": 1}]
+    }));
+
+    let mut val = src.clone();
+    val.prune_with(&PruneOptions::new().include("/items/*/tags").include("/name"));
+    assert_eq!(val, json!({
+        "patch": {"email": null, "address": {"city": null}},
+        "items": [{"id": null}, {"id": 1}]
+    }));
+
+    let mut val = src;
+    val.prune_with(&PruneOptions::null_and_empty().cascade(true).include("/**").exclude("/patch/address"));
+    assert_eq!(val, json!({"patch": {"address": {}}, "items": [{"id": 1}]}));
+}
+
+#[test]
+#[should_panic(expected = "invalid JSON pointer pattern \"patch/**\"")]
+fn test_invalid_path_pattern() {
+    PruneOptions::new().exclude("patch/**");
+}
+
+#[test]
+fn test_deep_merge() {
+    let left = json!({
+        "name": "car",
+        "specs": {"engine": {"hp": 100}, "colors": ["red"]},
+        "owners": [{"id": 1, "since": 2010}, {"id": 2}],
+        "points": [1, 2, 3]
+    });
+    let right = json!({
+        "name": "truck",
+        "specs": {"engine": {"cc": 2000}, "colors": ["red", "blue"]},
+        "owners": [{"id": 2, "since": 2015}, {"id": 3}],
+        "points": [4]
+    });
+
+    let mut val = left.clone();
+    val.deep_merge(right.clone(), &MergeOptions::new()).unwrap();
+    assert_eq!(val, json!({
+        "name": "truck",
+        "specs": {"engine": {"hp": 100, "cc": 2000}, "colors": ["red", "blue"]},
+        "owners": [{"id": 2, "since": 2015}, {"id": 3}],
+        "points": [4]
+    }));
+
+    let mut val = left.clone();
+    let options = MergeOptions::new()
+        .arrays(ArrayMerge::Append)
+        .arrays_at("/specs/colors", ArrayMerge::Union)
+        .arrays_at("/owners", ArrayMerge::ByKey("/id".to_string()))
+        .arrays_at("/points", ArrayMerge::ByIndex)
+        .conflicts(Conflict::LeftWins);
+    val.deep_merge(right.clone(), &options).unwrap();
+    assert_eq!(val, json!({
+        "name": "car",
+        "specs": {"engine": {"hp": 100, "cc": 2000}, "colors": ["red", "blue"]},
+        "owners": [{"id": 1, "since": 2010}, {"id": 2, "since": 2015}, {"id": 3}],
+        "points": [1, 2, 3]
+    }));
+
+    let mut val = left.clone();
+    let options = MergeOptions::new()
+        .arrays(ArrayMerge::ByIndex)
+        .conflicts(Conflict::Error)
+        .conflicts_at("/name", Conflict::Collect);
+    match val.deep_merge(right.clone(), &options) {
+        Err(Error::IncompatibleMerge { path }) => assert_eq!(path, "/owners/0/id"),
+        r => panic!("unexpected result: {:?}", r),
+    }
+    assert_eq!(val, left);
+
+    let mut val = left.clone();
+    match val.deep_merge(right, &MergeOptions::new().arrays_at("/owners", ArrayMerge::ByKey("id".to_string()))) {
+        Err(Error::InvalidPointer { path }) => assert_eq!(path, "id"),
+        r => panic!("unexpected result: {:?}", r),
+    }
+    assert_eq!(val, left);
+
+    let mut val = json!({"name": "car", "specs": 1});
+    val.deep_merge(json!({"name": "truck", "specs": {"hp": 1}}), &MergeOptions::new().conflicts(Conflict::Collect)).unwrap();
+    assert_eq!(val, json!({"name": ["car", "truck"], "specs": [1, {"hp": 1}]}));
+}
+
+#[test]
+fn test_merge_patch() {
+    // test cases of the appendix A of RFC 7396
+    let cases = [
+        (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+        (json!({"a": "b"}), json!({"b": "c"}), json!({"a": "b", "b": "c"})),
+        (json!({"a": "b"}), json!({"a": null}), json!({})),
+        (json!({"a": "b", "b": "c"}), json!({"a": null}), json!({"b": "c"})),
+        (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+        (json!({"a": "c"}), json!({"a": ["b"]}), json!({"a": ["b"]})),
+        (json!({"a": {"b": "c"}}), json!({"a": {"b": "d", "c": null}}), json!({"a": {"b": "d"}})),
+        (json!({"a": [{"b": "c"}]}), json!({"a": [1]}), json!({"a": [1]})),
+        (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+        (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+        (json!({"a": "foo"}), json!(null), json!(null)),
+        (json!({"a": "foo"}), json!("bar"), json!("bar")),
+        (json!({"e": null}), json!({"a": 1}), json!({"e": null, "a": 1})),
+        (json!([1, 2]), json!({"a": "b", "c": null}), json!({"a": "b"})),
+        (json!({}), json!({"a": {"bb": {"ccc": null}}}), json!({"a": {"bb": {}}})),
+    ];
+    for (target, patch, result) in cases.iter() {
+        let mut val = target.clone();
+        val.merge_patch(patch);
+        assert_eq!(&val, result);
+    }
+
+    let from = json!({"a": 1, "b": {"c": [1], "d": "x"}, "e": "y"});
+    let to = json!({"a": 1, "b": {"c": [1, 2], "f": {"g": true}}, "h": [null]});
+    let patch = create_merge_patch(&from, &to);
+    assert_eq!(patch, json!({"b": {"c": [1, 2], "d": null, "f": {"g": true}}, "e": null, "h": [null]}));
+    let mut val = from.clone();
+    val.merge_patch(&patch);
+    assert_eq!(val, to);
+    assert_eq!(create_merge_patch(&from, &from), json!({}));
+}
+
+#[test]
+fn test_apply_patch() {
+    // test cases of the appendix A of RFC 6902
+    let cases = [
+        (json!({"foo": "bar"}), json!([{"op": "add", "path": "/baz", "value": "qux"}]), json!({"baz": "qux", "foo": "bar"})),
+        (json!({"foo": ["bar", "baz"]}), json!([{"op": "add", "path": "/foo/1", "value": "qux"}]), json!({"foo": ["bar", "qux", "baz"]})),
+        (json!({"baz": "qux", "foo": "bar"}), json!([{"op": "remove", "path": "/baz"}]), json!({"foo": "bar"})),
+        (json!({"foo": ["bar", "qux", "baz"]}), json!([{"op": "remove", "path": "/foo/1"}]), json!({"foo": ["bar", "baz"]})),
+        (json!({"baz": "qux", "foo": "bar"}), json!([{"op": "replace", "path": "/baz", "value": "boo"}]), json!({"baz": "boo", "foo": "bar"})),
+        (
+            json!({"foo": {"bar": "baz", "waldo": "fred"}, "qux": {"corge": "grault"}}),
+            json!([{"op": "move", "from": "/foo/waldo", "path": "/qux/thud"}]),
+            json!({"foo": {"bar": "baz"}, "qux": {"corge": "grault", "thud": "fred"}}),
+        ),
+        (json!({"foo": ["all", "grass", "cows", "eat"]}), json!([{"op": "move", "from": "/foo/1", "path": "/foo/3"}]), json!({"foo": ["all", "cows", "eat", "grass"]})),
+        (json!({"foo": ["bar"]}), json!([{"op": "add", "path": "/foo/-", "value": ["abc", "def"]}]), json!({"foo": ["bar", ["abc", "def"]]})),
+        (json!({"/": 9, "~1": 10}), json!([{"op": "test", "path": "/~01", "value": 10.0}]), json!({"/": 9, "~1": 10})),
+        (json!({"foo": 1}), json!([{"op": "copy", "from": "/foo", "path": ""}]), json!(1)),
+    ];
+    for (target, patch, result) in cases.iter() {
+        let patch: Vec<PatchOperation> = from_value(patch.clone()).unwrap();
+        let mut val = target.clone();
+        val.apply_patch(&patch).unwrap();
+        assert_eq!(&val, result);
+    }
+
+    let errors = [
+        (json!([{"op": "add", "path": "/baz/bat", "value": "qux"}]), "/baz/bat: path not found"),
+        (json!([{"op": "add", "path": "/foo/0/bat", "value": "qux"}]), "/foo/0/bat: expected object or array, found string"),
+        (json!([{"op": "add", "path": "/foo/3", "value": "qux"}]), "/foo/3: path not found"),
+        (json!([{"op": "remove", "path": "/foo/01"}]), "/foo/01: invalid JSON pointer"),
+        (json!([{"op": "replace", "path": "foo", "value": 1}]), "foo: invalid JSON pointer"),
+        (json!([{"op": "move", "from": "/foo", "path": "/foo/0"}]), "/foo/0: invalid JSON pointer"),
+        (json!([{"op": "remove", "path": "/foo/0"}, {"op": "test", "path": "/foo/0", "value": "bar"}]), "/foo/0: test failed"),
+    ];
+    for (patch, message) in errors.iter() {
+        let patch: Vec<PatchOperation> = from_value(patch.clone()).unwrap();
+        let mut val = json!({"foo": ["bar", "baz"]});
+        assert_eq!(val.apply_patch(&patch).unwrap_err().to_string(), *message);
+        assert_eq!(val, json!({"foo": ["bar", "baz"]}));
+    }
+}
+
+#[test]
+fn test_patch_diff() {
+    let from: Value = from_str(DATA).unwrap();
+    let mut to: Value = from_str(RESULT_SKIP_NULL_AND_EMPTY_AND_DEDUPLICATE).unwrap();
+    to[1]["key3"] = json!({"a": [1, 2]});
+    to.as_array_mut().unwrap().push(json!("new"));
+
+    let patch = diff(&from, &to);
+    assert_eq!(patch, from_value::<Vec<PatchOperation>>(json!([
+        {"op": "remove", "path": "/0/key1"},
+        {"op": "remove", "path": "/0/key3"},
+        {"op": "remove", "path": "/0/key4"},
+        {"op": "remove", "path": "/0/key5/3"},
+        {"op": "remove", "path": "/1/key2"},
+        {"op": "add", "path": "/1/key3", "value": {"a": [1, 2]}},
+        {"op": "replace", "path": "/2", "value": "new"}
+    ])).unwrap());
+    let mut val = from.clone();
+    val.apply_patch(&patch).unwrap();
+    assert_eq!(val, to);
+
+    assert!(diff(&to, &to).is_empty());
+    let mut val = json!(1);
+    val.apply_patch(&diff(&json!(1), &json!({"a": 1}))).unwrap();
+    assert_eq!(val, json!({"a": 1}));
+}
+
+#[test]
+fn test_diff() {
+    let from: Value = from_str(DATA).unwrap();
+    let mut to: Value = from_str(RESULT_DEDUP).unwrap();
+    to[0]["key2"] = json!(["there is a value"]);
+    to[1]["key1"] = json!("other value");
+    to[1]["key3"] = json!(1.5);
+
+    let diff = from.diff(&to);
+    assert_eq!(diff, vec![
+        Difference::TypeChanged { path: "/0/key2".to_string(), from: json!("there is a value"), to: json!(["there is a value"]) },
+        Difference::Removed { path: "/0/key5/3".to_string(), value: json!(3) },
+        Difference::Changed { path: "/1/key1".to_string(), from: json!("value in here"), to: json!("other value") },
+        Difference::Added { path: "/1/key3".to_string(), value: json!(1.5) },
+        Difference::Removed { path: "/2".to_string(), value: from[2].clone() },
+    ]);
+    assert_eq!(DiffReport(&diff).to_string(), [
+        "! /0/key2: string \"there is a value\" -> array [\"there is a value\"]",
+        "- /0/key5/3: 3",
+        "~ /1/key1: \"value in here\" -> \"other value\"",
+        "+ /1/key3: 1.5",
+        "- /2: {\"key1\":\"value in here\",\"key2\":null}",
+        "",
+    ].join("\n"));
+
+    let options = DiffOptions::new().ignore("/*/key1").ignore("/0/key2").ignore("/2");
+    assert_eq!(from.diff_with(&to, &options), vec![
+        Difference::Removed { path: "/0/key5/3".to_string(), value: json!(3) },
+        Difference::Added { path: "/1/key3".to_string(), value: json!(1.5) },
+    ]);
+
+    let a = json!([[1, 2], {"x": 1.0}, 3]);
+    let b = json!([3, {"x": 1.001}, [2, 1], 4]);
+    assert_eq!(a.diff_with(&b, &DiffOptions::new().ignore_array_order(true).epsilon(0.01)), vec![
+        Difference::Added { path: "/3".to_string(), value: json!(4) },
+    ]);
+    assert_eq!(DiffReport(&json!(1).diff(&json!(2))).to_string(), "~ (root): 1 -> 2\n");
+}
+
+#[test]
+fn test_pointer_helpers() {
+    let mut val = Value::Null;
+    val.set_path("/a/b/0/c", json!(1)).unwrap();
+    assert_eq!(val, json!({"a": {"b": [{"c": 1}]}}));
+    val.set_path("/a/b/1/-", json!("x")).unwrap();
+    val.set_path("/a/d~1e", json!(true)).unwrap();
+    val.set_path("/a/n", Value::Null).unwrap();
+    val.set_path("/a/n/0", json!(2)).unwrap();
+    assert_eq!(val, json!({"a": {"b": [{"c": 1}, ["x"]], "d/e": true, "n": [2]}}));
+    assert_eq!(val.set_path("/a/b/0/c", json!(3)).unwrap(), Some(json!(1)));
+
+    let err = val.set_path("/a/b/5", json!(1)).unwrap_err();
+    assert_eq!(err.to_string(), "/a/b/5: path not found");
+    let err = val.set_path("/a/d~1e/f", json!(1)).unwrap_err();
+    assert_eq!(err.to_string(), "/a/d~1e/f: expected object or array, found boolean");
+    let err = val.set_path("/a/b/x", json!(1)).unwrap_err();
+    assert_eq!(err.to_string(), "/a/b/x: invalid JSON pointer");
+    assert!(matches!(val.set_path("a", json!(1)), Err(Error::InvalidPointer { .. })));
+    let before = val.clone();
+    for path in ["/m/10", "/m/0/1", "/m/x/01/y/2", "/a/b/1/1/2", "/a/q/r/d~1e/1"] {
+        assert!(matches!(val.set_path(path, json!(1)), Err(Error::PathNotFound { .. })), "{}", path);
+        assert!(val.insert_path(path, json!(1)).is_err(), "{}", path);
+        assert_eq!(val, before, "{}", path);
+    }
+    assert!(matches!(val.set_path("/a/d~1e/x/y", json!(1)), Err(Error::ShapeMismatch { .. })));
+    assert_eq!(val, before);
+
+    val.insert_path("/a/b/0", json!(0)).unwrap();
+    val.insert_path("/a/new/-", json!(0)).unwrap();
+    assert_eq!(val["a"]["b"], json!([0, {"c": 3}, ["x"]]));
+    assert_eq!(val["a"]["new"], json!([0]));
+
+    assert_eq!(val.take_path("/a/b/1/c").unwrap(), json!(3));
+    assert_eq!(val.remove_path("/a/b/2").unwrap(), json!(["x"]));
+    assert_eq!(val.remove_path("/a/new").unwrap(), json!([0]));
+    assert_eq!(val, json!({"a": {"b": [0, {"c": null}], "d/e": true, "n": [2]}}));
+    assert!(matches!(val.take_path("/a/b/2"), Err(Error::PathNotFound { .. })));
+
+    assert_eq!(val.set_path("", json!(1)).unwrap(), Some(json!({"a": {"b": [0, {"c": null}], "d/e": true, "n": [2]}})));
+    assert_eq!(val, json!(1));
+}
+
+#[test]
+fn test_query() {
+    let val = json!({
+        "store": {
+            "book": [
+                {"category": "reference", "author": "Nigel Rees", "title": "Sayings of the Century", "price": 8.95},
+                {"category": "fiction", "author": "Evelyn Waugh", "title": "Sword of Honour", "price": 12.99},
+                {"category": "fiction", "author": "Herman Melville", "title": "Moby Dick", "isbn": "0-553-21311-3", "price": 8.99},
+                {"category": "fiction", "author": "J. R. R. Tolkien", "title": "The Lord of the Rings", "isbn": "0-395-19395-8", "price": 22.99}
+            ],
+            "bicycle": {"color": "red", "price": 399}
+        }
+    });
+    let paths = |q: &str| val.query(q).unwrap().into_iter().map(|m| m.path).collect::<Vec<String>>();
+    let values = |q: &str| val.query(q).unwrap().into_iter().map(|m| m.value.clone()).collect::<Vec<Value>>();
+
+    assert_eq!(values("$.store.book[*].author").len(), 4);
+    assert_eq!(values("$..author").len(), 4);
+    assert_eq!(values("$.store..price").len(), 5);
+    assert_eq!(values("$..book[2].author"), vec![json!("Herman Melville")]);
+    assert_eq!(values("$..book[-1].title"), vec![json!("The Lord of the Rings")]);
+    assert_eq!(paths("$..book[:2]"), vec!["$['store']['book'][0]", "$['store']['book'][1]"]);
+    assert_eq!(paths("$.store.book[::-2]"), vec!["$['store']['book'][3]", "$['store']['book'][1]"]);
+    assert_eq!(paths("$.store.book[0, 'x', 0]['title']").len(), 2);
+    assert_eq!(values("$..book[?@.isbn].title"), vec![json!("Moby Dick"), json!("The Lord of the Rings")]);
+    assert_eq!(values("$..book[?(@.price < 10 && !(@.category == 'fiction'))].title"), vec![json!("Sayings of the Century")]);
+    assert_eq!(values("$..book[?@.price == 8.95 || @.price > 20].price"), vec![json!(8.95), json!(22.99)]);
+    assert_eq!(values("$..book[?@.price < $.store.bicycle.price].price").len(), 4);
+    assert_eq!(values("$..book[?length(@.title) == 9].author"), vec![json!("Herman Melville")]);
+    assert_eq!(values("$.store[?count(@.*) == 2].color"), vec![json!("red")]);
+    #[cfg(feature = "regex")]
+    {
+        assert_eq!(values("$..book[?match(@.author, 'J.*')].price"), vec![json!(22.99)]);
+        assert_eq!(values("$..book[?search(@.title, 'of')].price").len(), 3);
+        assert_eq!(values("$..book[?search(@.title, 'of[')].price").len(), 0);
+        assert_eq!(values("$..book[?match(@.author, @.author)].price").len(), 4);
+    }
+    #[cfg(not(feature = "regex"))]
+    assert!(matches!(val.query("$..book[?match(@.author, 'J.*')]"), Err(Error::InvalidQuery { .. })));
+    assert_eq!(values("$..book[?value(@..isbn) == '0-553-21311-3'].price"), vec![json!(8.99)]);
+    assert_eq!(values(r#"$["store"]["bicycle"].*"#), vec![json!("red"), json!(399)]);
+    assert_eq!(values("$.nothing"), Vec::<Value>::new());
+    assert_eq!(values("$[?@ == 1]"), Vec::<Value>::new());
+    assert_eq!(json!({"a": "it's"}).query("$.*").unwrap()[0].path, "$['a']");
+    assert_eq!(json!({"it's\n": 1}).query("$.*").unwrap()[0].path, r"$['it\'s\n']");
+    assert_eq!(json!([1, 1.0, "1"]).query("$[?@ == 1]").unwrap().len(), 2);
+
+    for invalid in ["", "$.", "$[01]", "$[-0]", "$..", " $", "$[?@.a == @.*]", "$[?length(@.*) > 1]", "$[?count(@) ]", "$[?foo(@)]", "$['a"] {
+        assert!(matches!(val.query(invalid), Err(Error::InvalidQuery { query, .. }) if query == invalid), "{}", invalid);
+    }
+    assert_eq!(val.query("$[1").unwrap_err().to_string(), "invalid JSONPath query \"$[1\", expected `,` at position 3");
+    let nested = |n: usize| format!("$[?{}@{}]", "(".repeat(n), ")".repeat(n));
+    assert!(val.query(&nested(MAX_DEPTH - 1)).is_ok());
+    assert!(matches!(val.query(&nested(MAX_DEPTH)), Err(Error::InvalidQuery { message, .. }) if message.starts_with("nesting deeper")));
+    assert!(matches!(val.query(&format!("${}{}", "[?@".repeat(100_000), "]".repeat(100_000))), Err(Error::InvalidQuery { .. })));
+
+    let mut val = val;
+    let mut seen = vec![];
+    let n = val
+        .query_mut("$..book[?@.price > 10].price", |path, v| {
+            seen.push(path.to_string());
+            *v = json!(10);
+        })
+        .unwrap();
+    assert_eq!(n, 2);
+    assert_eq!(seen, vec!["$['store']['book'][1]['price']", "$['store']['book'][3]['price']"]);
+    assert_eq!(val.query("$..book[?@.price == 10]").unwrap().len(), 2);
+    assert_eq!(val.query_mut("$..book[0]", |_, v| *v = Value::Null).unwrap(), 1);
+    assert_eq!(val.query_mut("$.store.book[0, 0]", |_, v| *v = Value::Null).unwrap(), 2);
+    assert_eq!(val["store"]["book"][0], Value::Null);
+
+    let mut val = json!([[1, 2, 3]]);
+    let mut seen = vec![];
+    let n = val
+        .query_mut("$..*", |path, v| {
+            seen.push((path.to_string(), v.clone()));
+            if let Value::Array(arr) = v {
+                arr.remove(0);
+            }
+        })
+        .unwrap();
+    assert_eq!(n, 4);
+    assert_eq!(seen, vec![
+        ("$[0][0]".to_string(), json!(1)),
+        ("$[0][1]".to_string(), json!(2)),
+        ("$[0][2]".to_string(), json!(3)),
+        ("$[0]".to_string(), json!([1, 2, 3])),
+    ]);
+    assert_eq!(val, json!([[2, 3]]));
+}
+
+#[test]
+fn test_flatten() {
+    let values = [
+        json!(null),
+        json!(1),
+        json!("a.b"),
+        json!([]),
+        json!({}),
+        json!([[1, [2]], {}]),
+        json!({"": {"": 1}, "0": [null, {"1": []}], "a.b": {"c\\": "d"}, "[x]": true, "e": {}}),
+        json!({"a": {"b": [{"c": 1}, 2]}, "sep//": [[{}]], "\\e": 1, "ee": 2}),
+        json!({"foo_": {"bar": 1}, "_": {"_x_": {"__": 2}}, "/": {"/": 3}}),
+    ];
+    let options = [
+        FlattenOptions::new(),
+        FlattenOptions::new().arrays(ArrayIndexStyle::Brackets),
+        FlattenOptions::new().separator("//").arrays(ArrayIndexStyle::Brackets),
+        FlattenOptions::new().separator("_").max_depth(1),
+        FlattenOptions::new().separator("__"),
+    ];
+    for val in &values {
+        for opts in &options {
+            let flat = val.flatten(opts).unwrap();
+            assert!(flat.as_object().unwrap().values().all(|v| !v.is_object() || v.as_object().unwrap().is_empty() || opts.max_depth.is_some()));
+            assert_eq!(&flat.unflatten(opts).unwrap(), val, "{} with {:?}", flat, opts);
+        }
+    }
+
+    let val = values[6].clone();
+    assert_eq!(val.flatten(&FlattenOptions::new()).unwrap(), json!({
+        "\\e.\\e": 1, "\\0.0": null, "\\0.1.\\1": [], "a\\.b.c\\\\": "d", "[x]": true, "e": {},
+    }));
+    assert_eq!(val.flatten(&options[1]).unwrap(), json!({
+        "\\e.\\e": 1, "0[0]": null, "0[1].1": [], "a\\.b.c\\\\": "d", "\\[x\\]": true, "e": {},
+    }));
+    assert_eq!(json!([[1]]).flatten(&options[1]).unwrap(), json!({"[0][0]": 1}));
+    assert_eq!(json!(1).flatten(&options[0]).unwrap(), json!({"": 1}));
+    assert_eq!(values[8].flatten(&options[4]).unwrap(), json!({"foo\\___bar": 1, "\\___\\_x\\___\\_\\_": 2, "/__/": 3}));
+    assert_eq!(values[7].flatten(&options[3]).unwrap(), json!({"a": {"b": [{"c": 1}, 2]}, "sep//": [[{}]], "\\\\e": 1, "ee": 2}));
+
+    assert_eq!(json!({"a.1": 1, "a.3": 2}).unflatten(&options[0]).unwrap(), json!({"a": [null, 1, null, 2]}));
+    for invalid in ["a..b", "a.", ".a", "a\\", "a[0]b", "a.[0]", "a[x]", "a]"] {
+        assert!(matches!(json!({ invalid: 1 }).unflatten(&options[1]), Err(Error::InvalidKey { key }) if key == invalid), "{}", invalid);
+    }
+    let err = json!({"a": 1, "a.b": 2}).unflatten(&options[0]).unwrap_err();
+    assert_eq!(err.to_string(), "/a: keys \"a\" and \"a.b\" collide");
+    for flat in [json!({"a.b": 2, "a": 1}), json!({"a": {}, "a.b": 2}), json!({"a": null, "a.b": 2}), json!({"a.b": 2, "a.\\b": 1})] {
+        assert!(matches!(flat.unflatten(&options[0]), Err(Error::KeyCollision { path, .. }) if path.starts_with("/a")), "{}", flat);
+    }
+    let err = json!({"a.0": 1, "a.b": 2}).unflatten(&options[0]).unwrap_err();
+    assert_eq!(err.to_string(), "/a: expected object, found array");
+    let err = json!({"a.99999": 1}).unflatten(&options[0]).unwrap_err();
+    assert_eq!(err.to_string(), "/a/99999: array padding limit of 65536 exceeded");
+    assert!(matches!(json!([1]).unflatten(&options[0]), Err(Error::ShapeMismatch { .. })));
+    for invalid in ["", "e", "-e-", "\\", "[", "1"] {
+        let options = FlattenOptions::new().separator(invalid);
+        assert!(matches!(values[6].flatten(&options), Err(Error::InvalidSeparator { separator }) if separator == invalid), "{}", invalid);
+        assert!(matches!(json!({"a": 1}).unflatten(&options), Err(Error::InvalidSeparator { .. })), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_rename_keys() {
+    let keys = ["fooBar", "FooBar", "foo-bar", "foo_bar", "FOO_BAR", "foo bar", "_fooBar_"];
+    let expected = [
+        (Case::Snake, "foo_bar"),
+        (Case::Camel, "fooBar"),
+        (Case::Pascal, "FooBar"),
+        (Case::Kebab, "foo-bar"),
+        (Case::ScreamingSnake, "FOO_BAR"),
+    ];
+    for (case, name) in expected {
+        for key in &keys[..6] {
+            assert_eq!(case.convert(key), name, "{} to {:?}", key, case);
+        }
+        assert_eq!(case.convert(keys[6]), format!("_{}_", name));
+    }
+    assert_eq!(Case::Snake.convert("HTTPServerError"), "http_server_error");
+    assert_eq!(Case::Camel.convert("user_id2Value"), "userId2Value");
+    assert_eq!(Case::Pascal.convert("élanVital"), "ÉlanVital");
+    assert_eq!(Case::Snake.convert("__"), "__");
+    assert_eq!(Case::Snake.convert(""), "");
+
+    let mut val = json!({"userId": 1, "Meta": {"freeForm": {"keepMe": 1}}, "list": [{"innerKey": [{"deepKey": true}]}]});
+    val.rename_keys_with(&RenameOptions::new().skip("/Meta/freeForm")).unwrap();
+    assert_eq!(val, json!({"user_id": 1, "meta": {"free_form": {"keepMe": 1}}, "list": [{"inner_key": [{"deep_key": true}]}]}));
+    val.rename_keys_with(&RenameOptions::new().case(Case::Kebab).skip("/list/*/*/**")).unwrap();
+    assert_eq!(val, json!({"user-id": 1, "meta": {"free-form": {"keep-me": 1}}, "list": [{"inner-key": [{"deep_key": true}]}]}));
+
+    let mut val = json!({"a": {"fooBar": 1, "foo_bar": 2}, "b": 1});
+    let err = val.rename_keys(Case::Snake).unwrap_err();
+    assert!(matches!(&err, Error::KeyCollision { path, .. } if path == "/a/foo_bar"));
+    assert_eq!(err.to_string(), r#"/a/foo_bar: keys "fooBar" and "foo_bar" collide"#);
+    assert_eq!(val, json!({"a": {"fooBar": 1, "foo_bar": 2}, "b": 1}));
+}
+
+#[test]
+fn test_remap() {
+    let mut val = json!({
+        "user": {"name": "foo", "tags": ["a", "b"]},
+        "items": [{"id": 1, "sku": "x"}, {"id": 2}, {"sku": "z"}],
+        "groups": {"g1": {"users": [{"n": 1}]}, "g2": {"users": [{"n": 2}, {"n": 3}]}},
+    });
+    let mapping: Mapping = [
+        ("/user/name", "/fullName"),
+        ("/items/*/id", "/items/*/identifier"),
+        ("/groups/*/users/*/n", "/members/*/*"),
+        ("/user/tags/*", "/labels/-"),
+        ("/user/missing", "/x"),
+        ("/items/*/missing", "/x"),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(val.remap(&mapping).unwrap(), vec![
+        "/items/2/id",
+        "/user/missing",
+        "/items/0/missing",
+        "/items/1/missing",
+        "/items/2/missing",
+    ]);
+    assert_eq!(val, json!({
+        "user": {"tags": []},
+        "fullName": "foo",
+        "items": [{"identifier": 1, "sku": "x"}, {"identifier": 2}, {"sku": "z"}],
+        "groups": {"g1": {"users": [{}]}, "g2": {"users": [{}, {}]}},
+        "members": {"g1": [1], "g2": [2, 3]},
+        "labels": ["a", "b"],
+    }));
+
+    let before = val.clone();
+    let err = val.remap(&Mapping::new().map("/fullName", "/members/g1/x")).unwrap_err();
+    assert_eq!(err.to_string(), "/members/g1: expected object, found array");
+    assert_eq!(val, before);
+    let err = val.remap(&Mapping::new().map("/fullName", "/x").map("/labels", "/x")).unwrap_err();
+    assert_eq!(err.to_string(), "/x: keys \"/fullName\" and \"/labels\" collide");
+    let err = val.remap(&Mapping::new().map("/items/*/sku", "/sku")).unwrap_err();
+    assert_eq!(err.to_string(), "/sku: keys \"/items/0/sku\" and \"/items/2/sku\" collide");
+    assert_eq!(val, before);
+    let mut groups = json!({"groups": {"0": {"n": 1}, "5": {"n": 2}}, "list": [{"n": 3}]});
+    let mapping = Mapping::new().map("/groups/*/n", "/by/*").map("/list/*/n", "/ns/*");
+    assert!(groups.remap(&mapping).unwrap().is_empty());
+    assert_eq!(groups, json!({"groups": {"0": {}, "5": {}}, "by": {"0": 1, "5": 2}, "list": [{}], "ns": [3]}));
+    let mut empty = json!({"a": [], "b": 1});
+    assert_eq!(empty.remap(&Mapping::new().map("/a/*/x", "/x").map("/b/c", "/c").map("/z/*", "/z")).unwrap(), vec![
+        "/a/*/x", "/b/c", "/z/*",
+    ]);
+    assert!(matches!(val.remap(&Mapping::new().map("/items/*", "/*/*")), Err(Error::InvalidPointer { .. })));
+    assert!(matches!(val.remap(&Mapping::new().map("items", "/x")), Err(Error::InvalidPointer { .. })));
+    assert_eq!(val, before);
+}
+
+#[test]
+fn test_canonical() {
+    let val: Value = from_str(r#"{
+        "numbers": [1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+        "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+        "literals": [null, true, false]
+    }"#)
+    .unwrap();
+    assert_eq!(
+        val.to_canonical_string(),
+        r#"{"literals":[null,true,false],"numbers":[1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#
+    );
+
+    let val: Value = from_str(r#"{"\u20ac": 1, "\r": 2, "\ufb33": 3, "1": 4, "\ud83d\ude00": 5, "\u0080": 6, "\u00f6": 7}"#).unwrap();
+    assert_eq!(val.to_canonical_string(), "{\"\\r\":2,\"1\":4,\"\u{80}\":6,\"ö\":7,\"€\":1,\"😀\":5,\"\u{fb33}\":3}");
+
+    let numbers = [
+        (json!(0.0), "0"),
+        (json!(-0.0), "0"),
+        (json!(-7), "-7"),
+        (json!(1e-7), "1e-7"),
+        (json!(-1.5e-7), "-1.5e-7"),
+        (json!(1e20), "100000000000000000000"),
+        (json!(1e21), "1e+21"),
+        (json!(5e-324), "5e-324"),
+        (json!(1.7976931348623157e308), "1.7976931348623157e+308"),
+        (json!(9007199254740994u64), "9007199254740994"),
+        (json!(u64::MAX), "18446744073709552000"),
+        (json!(123.456), "123.456"),
+        (json!(333333333.3333333), "333333333.3333333"),
+        (json!(0.1), "0.1"),
+    ];
+    for (n, s) in numbers {
+        assert_eq!(n.to_canonical_string(), s);
+    }
+
+    let mut buf = vec![];
+    json!({"b": [], "a": {}}).write_canonical(&mut buf).unwrap();
+    assert_eq!(buf, br#"{"a":{},"b":[]}"#);
+}
+
+#[test]
+fn test_fingerprint() {
+    let a = json!({"id": 1, "tags": ["x", "Y"], "nested": {"b": null, "a": [1.5, -0.0]}});
+    let b: Value = from_str(r#"{"nested": {"a": [1.5, 0.0], "b": null}, "tags": ["x", "Y"], "id": 1}"#).unwrap();
+    assert_eq!(a.fingerprint(), b.fingerprint());
+    assert_eq!(
+        a.fingerprint().to_hex(),
+        "7c1f3ff13f5cd0e6e8d92543f140c18af325f106581f477d8713a000d468b76d"
+    );
+    assert_eq!(a.fingerprint().to_u128(), u128::from_str_radix(&a.fingerprint().to_hex()[..32], 16).unwrap());
+
+    let distinct = [
+        json!(null), json!(false), json!(true), json!(0), json!(0.0), json!(-1), json!(""), json!("0"),
+        json!([]), json!({}), json!([[]]), json!([null]), json!({"": null}), json!(["a", "b"]), json!(["ab"]),
+        json!({"a": "b"}), json!({"ab": ""}), json!([{}, []]), json!([[], {}]),
+    ];
+    let prints = distinct.iter().map(|v| v.fingerprint()).collect::<HashSet<Fingerprint>>();
+    assert_eq!(prints.len(), distinct.len());
+
+    let options = FingerprintOptions::new()
+        .strings(StringCompare::CaseFolded)
+        .normalize_whitespace(true)
+        .numbers(NumberCompare::Semantic);
+    let c = json!({"id": 1.0, "tags": [" X ", "y"], "nested": {"b": null, "a": [1.5, 0]}});
+    assert_ne!(a.fingerprint(), c.fingerprint());
+    assert_eq!(a.fingerprint_with(&options), c.fingerprint_with(&options));
+    assert_ne!(a.fingerprint_with(&options), json!({"id": 2}).fingerprint_with(&options));
+    assert_eq!(json!(1e3).fingerprint_with(&options), json!(1000u64).fingerprint_with(&options));
+}
+
+#[test]
+fn test_key_order_hashing() {
+    // with the `preserve_order` feature the keys keep the order of the source
+    let mut val: Value = from_str(r#"[
+        {"a": 1, "b": {"x": [1, {"p": 1, "q": 2}], "y": null}},
+        {"b": {"y": null, "x": [1, {"q": 2, "p": 1}]}, "a": 1},
+        {"b": {"y": null, "x": [{"q": 2, "p": 1}, 1]}, "a": 1}
+    ]"#)
+    .unwrap();
+    let arr = val.as_array().unwrap().clone();
+    fn hash<T: Hash>(v: &T) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        v.hash(&mut hasher);
+        hasher.finish()
+    }
+    let compare = Compare::default();
+    assert_eq!(hash(&DedupeHashValue(&arr[0], &compare)), hash(&DedupeHashValue(&arr[1], &compare)));
+    assert_ne!(hash(&DedupeHashValue(&arr[0], &compare)), hash(&DedupeHashValue(&arr[2], &compare)));
+    assert_eq!(hash(&HashValue(arr[0].clone(), &compare)), hash(&HashValue(arr[2].clone(), &compare)));
+    assert!(HashValue(arr[0].clone(), &compare) == HashValue(arr[2].clone(), &compare));
+
+    val.dedup();
+    assert_eq!(val, json!([arr[0], arr[2]]));
+
+    let mut val: Value = from_str(r#"[{"id": 1, "name": "a"}, {"name": "b", "id": 1}, {"name": "a", "id": 1}]"#).unwrap();
+    val.merge_similar();
+    assert_eq!(val, json!([{"id": 1, "name": ["a", "b"]}]));
+}
+
+#[cfg(feature = "preserve_order")]
+#[test]
+fn test_removal_keeps_key_order() {
+    let keys = |v: &Value| v.as_object().unwrap().keys().cloned().collect::<Vec<String>>();
+    let src = json!({"a": 1, "b": 2, "c": 3, "d": 4});
+
+    let mut val = src.clone();
+    val.remove_path("/a").unwrap();
+    assert_eq!(keys(&val), ["b", "c", "d"]);
+
+    let mut val = src.clone();
+    val.merge_patch(&json!({"a": null}));
+    assert_eq!(keys(&val), ["b", "c", "d"]);
+
+    let mut val = src.clone();
+    val.remap(&Mapping::new().map("/a", "/e")).unwrap();
+    assert_eq!(keys(&val), ["b", "c", "d", "e"]);
+
+    let patch: Vec<PatchOperation> = from_value(json!([
+        {"op": "remove", "path": "/a"},
+        {"op": "move", "from": "/b", "path": "/f"},
+    ]))
+    .unwrap();
+    let mut val = src;
+    val.apply_patch(&patch).unwrap();
+    assert_eq!(keys(&val), ["c", "d", "f"]);
+}
+
+#[test]
+fn test_array_compare() {
+    let src = json!([
+        {"id": 1, "tags": ["a", "b"], "perms": [["r", "w"], ["x"]]},
+        {"id": 1, "tags": ["b", "a"], "perms": [["r", "w"], ["x"]]},
+        {"id": 1, "tags": ["b", "a", "a"], "perms": [["x"], ["w", "r"]]},
+    ]);
+    let dedup = |options: DedupOptions| {
+        // only the top level array, so that the tags are compared with their duplicates
+        let mut val = src.clone();
+        val.dedup_with(&options.scope(DedupScope::TopLevel));
+        val.as_array().unwrap().len()
+    };
+    assert_eq!(dedup(DedupOptions::new()), 3);
+    assert_eq!(dedup(DedupOptions::new().arrays_at("/tags", ArrayCompare::Multiset)), 2);
+    assert_eq!(dedup(DedupOptions::new().arrays_at("/tags", ArrayCompare::Set)), 2);
+    assert_eq!(dedup(DedupOptions::new().arrays(ArrayCompare::Set)), 1);
+    assert_eq!(dedup(DedupOptions::new().arrays(ArrayCompare::Multiset)), 2);
+    assert_eq!(dedup(DedupOptions::new().arrays_at("/tags", ArrayCompare::Ordered).arrays(ArrayCompare::Set)), 3);
+    assert_eq!(dedup(DedupOptions::new().arrays_at("/tags", ArrayCompare::Set).arrays_at("/perms/**", ArrayCompare::Set)), 1);
+
+    let options = FingerprintOptions::new().arrays_at("/tags", ArrayCompare::Set);
+    let prints = src.as_array().unwrap().iter().map(|v| v.fingerprint_with(&options)).collect::<Vec<Fingerprint>>();
+    assert_eq!(prints[0], prints[1]);
+    assert_ne!(prints[0], prints[2]);
+    let options = options.arrays(ArrayCompare::Set);
+    assert_eq!(src[0].fingerprint_with(&options), src[2].fingerprint_with(&options));
+    assert_ne!(src[0].fingerprint(), src[1].fingerprint());
+
+    let mut val = json!([{"id": 1, "tags": ["a", "b"]}, {"id": 2, "tags": ["b", "a"]}]);
+    val.merge_similar_with(&MergeSimilarOptions::new().arrays_at("/tags", ArrayCompare::Multiset));
+    assert_eq!(val, json!([{"id": [1, 2], "tags": ["a", "b"]}]));
+
+    let a = json!({"list": [1, 2], "tags": ["a", "b", "b"]});
+    let b = json!({"list": [2, 1], "tags": ["b", "a", "c"]});
+    assert_eq!(a.diff_with(&b, &DiffOptions::new().arrays_at("/tags", ArrayCompare::Set)), vec![
+        Difference::Changed { path: "/list/0".to_string(), from: json!(1), to: json!(2) },
+        Difference::Changed { path: "/list/1".to_string(), from: json!(2), to: json!(1) },
+        Difference::Added { path: "/tags/2".to_string(), value: json!("c") },
+    ]);
+    assert_eq!(a.diff_with(&b, &DiffOptions::new().arrays(ArrayCompare::Multiset)), vec![
+        Difference::Removed { path: "/tags/2".to_string(), value: json!("b") },
+        Difference::Added { path: "/tags/2".to_string(), value: json!("c") },
+    ]);
+}
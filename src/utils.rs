@@ -1,20 +1,154 @@
+use crate::{
+    casefold,
+    dedup::{ArrayCompare, NumberCompare, StringCompare},
+    pointer::{self, PathPattern},
+};
 use ordered_float::NotNan;
-use serde_json::Value::{self, *};
+use serde_json::{
+    Map, Number,
+    Value::{self, *},
+};
 use std::{
+    borrow::Cow,
+    collections::hash_map::DefaultHasher,
+    fmt::Display,
     hash::{Hash, Hasher},
     string::String,
 };
 
-#[derive(Debug, PartialEq)]
-pub(crate) struct DedupeHashValue<'a>(pub &'a Value);
+/// Equality and hashing of values according to the comparison options
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Compare {
+    pub strings: StringCompare,
+    pub whitespace: bool,
+    pub numbers: NumberCompare,
+    pub epsilon: Option<f64>,
+    pub arrays: ArrayCompare,
+    /// Array comparisons at paths relative to the compared values
+    pub arrays_at: Vec<(PathPattern, ArrayCompare)>,
+}
 
-impl Eq for DedupeHashValue<'_> {}
+impl Compare {
+    /// Returns how the arrays at `path` are compared
+    pub fn arrays_for(&self, path: &str) -> ArrayCompare {
+        self.arrays_at
+            .iter()
+            .find(|(p, _)| p.matches(path))
+            .map_or(self.arrays, |(_, a)| *a)
+    }
 
-impl Hash for DedupeHashValue<'_> {
-    fn hash<H: Hasher>(&self, state: &mut H) {
-        match self.0 {
+    /// Returns the path of a child, which is only tracked when arrays are compared by path
+    pub fn child<T: Display>(&self, path: &str, token: T) -> String {
+        if self.arrays_at.is_empty() {
+            String::new()
+        } else {
+            pointer::push(path, &token.to_string())
+        }
+    }
+
+    /// Returns the form of `s` used for comparison
+    pub fn normalize_str<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        let s = if self.whitespace {
+            Cow::Owned(s.split_whitespace().collect::<Vec<&str>>().join(" "))
+        } else {
+            Cow::Borrowed(s)
+        };
+        match self.strings {
+            StringCompare::CaseSensitive => s,
+            StringCompare::CaseInsensitive => Cow::Owned(s.to_lowercase()),
+            StringCompare::CaseFolded => Cow::Owned(casefold::fold(&s)),
+        }
+    }
+
+    pub fn eq(&self, a: &Value, b: &Value) -> bool {
+        self.eq_at(a, b, "")
+    }
+
+    /// Compares two values found at `path`, relative to the compared values
+    pub fn eq_at(&self, a: &Value, b: &Value, path: &str) -> bool {
+        match (a, b) {
+            (Null, Null) => true,
+            (Bool(b1), Bool(b2)) => b1 == b2,
+            (Number(n1), Number(n2)) => self.eq_numbers(n1, n2),
+            (String(s1), String(s2)) => s1 == s2 || self.normalize_str(s1) == self.normalize_str(s2),
+            (Array(a1), Array(a2)) => match self.arrays_for(path) {
+                ArrayCompare::Ordered => {
+                    a1.len() == a2.len()
+                        && a1
+                            .iter()
+                            .zip(a2)
+                            .enumerate()
+                            .all(|(i, (v1, v2))| self.eq_at(v1, v2, &self.child(path, i)))
+                }
+                ArrayCompare::Multiset => {
+                    let mut paired = vec![false; a2.len()];
+                    a1.len() == a2.len()
+                        && a1.iter().enumerate().all(|(i, v1)| {
+                            let p = self.child(path, i);
+                            match (0..a2.len()).find(|&j| !paired[j] && self.eq_at(v1, &a2[j], &p)) {
+                                Some(j) => {
+                                    paired[j] = true;
+                                    true
+                                }
+                                None => false,
+                            }
+                        })
+                }
+                ArrayCompare::Set => {
+                    let within = |x: &[Value], y: &[Value]| {
+                        x.iter().enumerate().all(|(i, v)| {
+                            let p = self.child(path, i);
+                            y.iter().any(|w| self.eq_at(v, w, &p))
+                        })
+                    };
+                    within(a1, a2) && within(a2, a1)
+                }
+            },
+            (Object(o1), Object(o2)) => {
+                o1.len() == o2.len()
+                    && o1
+                        .iter()
+                        .all(|(k, v1)| o2.get(k).map_or(false, |v2| self.eq_at(v1, v2, &self.child(path, k))))
+            }
+            _ => false,
+        }
+    }
+
+    pub fn eq_numbers(&self, n1: &Number, n2: &Number) -> bool {
+        if let Some(epsilon) = self.epsilon {
+            return match (n1.as_f64(), n2.as_f64()) {
+                (Some(x), Some(y)) => (x - y).abs() <= epsilon,
+                _ => false,
+            };
+        }
+        match self.numbers {
+            NumberCompare::Exact => n1 == n2,
+            NumberCompare::Semantic => match (integral(n1), integral(n2)) {
+                (Some(x), Some(y)) => x == y,
+                _ => n1.as_f64() == n2.as_f64(),
+            },
+        }
+    }
+
+    pub fn hash<H: Hasher>(&self, val: &Value, state: &mut H) {
+        self.hash_at(val, state, "")
+    }
+
+    fn hash_at<H: Hasher>(&self, val: &Value, state: &mut H, path: &str) {
+        match val {
             Null => state.write_u32(3_221_225_473), // chosen randomly
             Bool(ref b) => b.hash(state),
+            // numbers within the tolerance of each other have to hash the same, so
+            // equality alone tells them apart
+            Number(_) if self.epsilon.is_some() => "number".hash(state),
+            Number(ref n) if self.numbers == NumberCompare::Semantic => {
+                "number".hash(state);
+                if let Some(x) = integral(n) {
+                    x.hash(state);
+                } else if let Some(x) = n.as_f64() {
+                    NotNan::new(x).unwrap().hash(state);
+                }
+            }
             Number(ref n) => {
                 "number".hash(state);
                 if let Some(x) = n.as_u64() {
@@ -27,72 +161,118 @@ impl Hash for DedupeHashValue<'_> {
                     NotNan::new(x).unwrap().hash(state);
                 }
             }
-            String(ref s) => s.to_lowercase().hash(state),
+            String(ref s) => self.normalize_str(s).hash(state),
             Array(ref v) => {
                 "array".hash(state);
-                for x in v {
-                    HashValue(x.clone()).hash(state);
+                let arrays = self.arrays_for(path);
+                if arrays == ArrayCompare::Ordered {
+                    for (i, x) in v.iter().enumerate() {
+                        self.hash_at(x, state, &self.child(path, i));
+                    }
+                    return;
+                }
+                // elements are hashed on their own and sorted, so that their order does not matter
+                let mut hashes = v
+                    .iter()
+                    .enumerate()
+                    .map(|(i, x)| {
+                        let mut hasher = DefaultHasher::new();
+                        self.hash_at(x, &mut hasher, &self.child(path, i));
+                        hasher.finish()
+                    })
+                    .collect::<Vec<u64>>();
+                hashes.sort_unstable();
+                if arrays == ArrayCompare::Set {
+                    hashes.dedup();
                 }
+                hashes.hash(state);
             }
             Object(ref map) => {
                 "map".hash(state);
-                for (k, v) in map {
-                    k.hash(state);
-                    DedupeHashValue(v).hash(state);
-                }
+                // entries are hashed on their own and combined with a commutative operation, so
+                // that the hash does not depend on the order of the keys, which `preserve_order` keeps
+                let entries = map.iter().fold(0u64, |acc, (k, v)| {
+                    let mut hasher = DefaultHasher::new();
+                    k.hash(&mut hasher);
+                    self.hash_at(v, &mut hasher, &self.child(path, k));
+                    acc.wrapping_add(hasher.finish())
+                });
+                state.write_usize(map.len());
+                state.write_u64(entries);
             }
         }
     }
 }
 
+/// Hash of a set of keys that does not depend on their order
+fn hash_keys<'a, H: Hasher>(keys: impl Iterator<Item = &'a String>, state: &mut H) {
+    let (len, sum) = keys.fold((0, 0u64), |(len, acc), k| {
+        let mut hasher = DefaultHasher::new();
+        k.hash(&mut hasher);
+        (len + 1, acc.wrapping_add(hasher.finish()))
+    });
+    state.write_usize(len);
+    state.write_u64(sum);
+}
+
+/// Value of an integer, or of a float without fractional part in the range of integers
+pub(crate) fn integral(n: &Number) -> Option<i128> {
+    if let Some(x) = n.as_u64() {
+        Some(x as i128)
+    } else if let Some(x) = n.as_i64() {
+        Some(x as i128)
+    } else {
+        n.as_f64()
+            .filter(|x| x.fract() == 0.0 && x.abs() <= u64::MAX as f64)
+            .map(|x| x as i128)
+    }
+}
+
 #[derive(Debug)]
-pub(crate) struct HashValue(pub Value);
+pub(crate) struct DedupeHashValue<'a>(pub &'a Value, pub &'a Compare);
 
-impl Eq for HashValue {}
+impl Eq for DedupeHashValue<'_> {}
 
-impl std::cmp::PartialEq for HashValue {
+impl PartialEq for DedupeHashValue<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.1.eq(self.0, other.0)
+    }
+}
+
+impl Hash for DedupeHashValue<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.1.hash(self.0, state)
+    }
+}
+
+/// Groups objects having the same keys, and other values being equal
+#[derive(Debug)]
+pub(crate) struct HashValue<'a>(pub Value, pub &'a Compare);
+
+impl Eq for HashValue<'_> {}
+
+impl std::cmp::PartialEq for HashValue<'_> {
     fn eq(&self, other: &Self) -> bool {
         match (&self.0, &other.0) {
-            (Null, Null) => true,
-            (Bool(b1), Bool(b2)) => b1 == b2,
-            (Number(b1), Number(b2)) => b1 == b2,
-            (String(b1), String(b2)) => b1 == b2,
-            (Array(b1), Array(b2)) => b1 == b2,
-            (Object(b1), Object(b2)) => {
-                b1.keys().collect::<Vec<&String>>() == b2.keys().collect::<Vec<&String>>()
-            }
-            _ => false,
+            (Object(b1), Object(b2)) => b1.len() == b2.len() && b1.keys().all(|k| b2.contains_key(k)),
+            (v1, v2) => self.1.eq(v1, v2),
         }
     }
 }
 
-impl Hash for HashValue {
+impl Hash for HashValue<'_> {
     fn hash<H: Hasher>(&self, state: &mut H) {
         match self.0 {
-            Null => state.write_u32(3_221_225_473), // chosen randomly
-            Bool(ref b) => b.hash(state),
-            Number(ref n) => {
-                if let Some(x) = n.as_u64() {
-                    x.hash(state);
-                } else if let Some(x) = n.as_i64() {
-                    x.hash(state);
-                } else if let Some(x) = n.as_f64() {
-                    // `f64` does not implement `Hash`. However, floats in JSON are guaranteed to be
-                    // finite, so we can use the `Hash` implementation in the `ordered-float` crate.
-                    NotNan::new(x).unwrap().hash(state);
-                }
-            }
-            String(ref s) => s.to_lowercase().hash(state),
-            Array(ref v) => {
-                for x in v {
-                    HashValue(x.clone()).hash(state);
-                }
-            }
-            Object(ref map) => {
-                for (k, _v) in map {
-                    k.hash(state);
-                }
-            }
+            Object(ref map) => hash_keys(map.keys(), state),
+            ref v => self.1.hash(v, state),
         }
     }
 }
+
+/// Removes a key from an object, keeping the other keys in order with `preserve_order`
+pub(crate) fn remove_key(obj: &mut Map<String, Value>, key: &str) -> Option<Value> {
+    #[cfg(feature = "preserve_order")]
+    return obj.shift_remove(key);
+    #[cfg(not(feature = "preserve_order"))]
+    return obj.remove(key);
+}